@@ -1,11 +1,16 @@
-use super::{Expression, visitor::ExpressionVisitor};
+use super::{Expression, span::{Span, SpanShift}, visitor::ExpressionVisitor};
 
-#[derive(Debug)]
-pub struct CommaExpression;
+#[derive(Debug, Default)]
+pub struct CommaExpression {
+    pub span: Span,
+    /// A comma after the last member or element, only kept when parsing lenient templates
+    /// losslessly. It is not written as JSON.
+    pub trailing: bool,
+}
 
 impl CommaExpression {
     pub fn new() -> CommaExpression {
-        CommaExpression{}
+        CommaExpression { span: Span::default(), trailing: false }
     }
 }
 
@@ -13,5 +18,12 @@ impl Expression for CommaExpression {
     fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
         visitor.visit_comma_expression(self);
     }
-}
 
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        self.span.shift(shift);
+    }
+}
@@ -0,0 +1,28 @@
+use super::{Expression, span::{Span, SpanShift}, visitor::ExpressionVisitor};
+
+/// A `//` or `/* */` comment, including its delimiters. Only lenient mode has comments.
+#[derive(Debug)]
+pub struct CommentExpression {
+    pub text: String,
+    pub span: Span,
+}
+
+impl CommentExpression {
+    pub fn new(text: &str) -> CommentExpression {
+        CommentExpression { text: String::from(text), span: Span::default() }
+    }
+}
+
+impl Expression for CommentExpression {
+    fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
+        visitor.visit_comment(self);
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        self.span.shift(shift);
+    }
+}
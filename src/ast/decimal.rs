@@ -0,0 +1,217 @@
+use super::errors::{InvalidNumberError, NumberConversionError};
+use std::convert::TryFrom;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// A JSON number kept exactly as written, for values that must round-trip without losing
+/// precision, such as 20 digit ids.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Decimal(String);
+
+impl Decimal {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn to_i64(&self) -> Result<i64, NumberConversionError> {
+        let n = self.to_i128()?;
+        i64::try_from(n).map_err(|_| self.conversion_error("i64", "Out of range"))
+    }
+
+    pub fn to_u64(&self) -> Result<u64, NumberConversionError> {
+        let n = self.to_i128()?;
+        u64::try_from(n).map_err(|_| self.conversion_error("u64", "Out of range"))
+    }
+
+    /// Converts numbers with an integral value, so `1.50e2` is 150 but `1.5` is an error.
+    pub fn to_i128(&self) -> Result<i128, NumberConversionError> {
+        let (negative, digits, exponent) = self.parts();
+        if digits.chars().all(|c| c == '0') {
+            return Ok(0);
+        }
+
+        let digits = if exponent >= 0 {
+            // Anything this long is out of range; checked here to avoid building huge strings.
+            if (digits.trim_start_matches('0').len() as i64).saturating_add(exponent) > 40 {
+                return Err(self.conversion_error("i128", "Out of range"));
+            }
+            format!("{}{}", digits, "0".repeat(exponent as usize))
+        } else {
+            let split = digits.len().saturating_sub(exponent.unsigned_abs() as usize);
+            if digits[split..].chars().any(|c| c != '0') {
+                return Err(self.conversion_error("i128", "Not an integer"));
+            }
+            String::from(&digits[..split])
+        };
+
+        let mut n: i128 = 0;
+        for d in digits.chars().map(|c| c.to_digit(10).unwrap() as i128) {
+            n = n.checked_mul(10)
+                .and_then(|n| if negative { n.checked_sub(d) } else { n.checked_add(d) })
+                .ok_or_else(|| self.conversion_error("i128", "Out of range"))?;
+        }
+
+        Ok(n)
+    }
+
+    /// Converts to the nearest f64. Numbers too large for an f64 are an error rather than
+    /// infinity.
+    pub fn to_f64(&self) -> Result<f64, NumberConversionError> {
+        match self.0.parse::<f64>() {
+            Ok(f) if f.is_finite() => Ok(f),
+            _ => Err(self.conversion_error("f64", "Out of range")),
+        }
+    }
+
+    /// The sign, the significant digits and the power of ten they are multiplied by.
+    fn parts(&self) -> (bool, String, i64) {
+        let s = self.0.as_str();
+        let (negative, s) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(i) => {
+                let e = &s[i + 1..];
+                // Exponents beyond i64 saturate, which is out of range either way.
+                let saturated = if e.starts_with('-') { i64::MIN } else { i64::MAX };
+                (&s[..i], e.parse::<i64>().unwrap_or(saturated))
+            },
+            None => (s, 0),
+        };
+
+        let (int, frac) = match mantissa.find('.') {
+            Some(i) => (&mantissa[..i], &mantissa[i + 1..]),
+            None => (mantissa, ""),
+        };
+
+        (negative, format!("{}{}", int, frac), exponent.saturating_sub(frac.len() as i64))
+    }
+
+    fn conversion_error(&self, target: &str, reason: &str) -> NumberConversionError {
+        NumberConversionError::new(&self.0, target, reason)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = InvalidNumberError;
+
+    /// Accepts exactly the JSON number grammar.
+    fn from_str(s: &str) -> Result<Decimal, InvalidNumberError> {
+        if is_json_number(s.as_bytes()) {
+            Ok(Decimal(String::from(s)))
+        } else {
+            Err(InvalidNumberError::new(s))
+        }
+    }
+}
+
+fn is_json_number(b: &[u8]) -> bool {
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while b.get(*i).is_some_and(u8::is_ascii_digit) {
+            *i += 1;
+        }
+        *i > start
+    };
+
+    let mut i = 0;
+    if b.get(i) == Some(&b'-') {
+        i += 1;
+    }
+
+    match b.get(i) {
+        Some(b'0') => i += 1,
+        Some(b'1'..=b'9') => {
+            digits(&mut i);
+        },
+        _ => return false,
+    }
+
+    if b.get(i) == Some(&b'.') {
+        i += 1;
+        if !digits(&mut i) {
+            return false;
+        }
+    }
+
+    if let Some(b'e') | Some(b'E') = b.get(i) {
+        i += 1;
+        if let Some(b'+') | Some(b'-') = b.get(i) {
+            i += 1;
+        }
+        if !digits(&mut i) {
+            return false;
+        }
+    }
+
+    i == b.len()
+}
+
+impl Display for Decimal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    macro_rules! valid_theory {
+        ($name:ident, $($s:literal),+) => {
+            #[test]
+            fn $name() {
+                $(assert!($s.parse::<Decimal>().is_ok(), "{} should be valid", $s);)+
+            }
+        }
+    }
+
+    macro_rules! invalid_theory {
+        ($name:ident, $($s:literal),+) => {
+            #[test]
+            fn $name() {
+                $(assert!($s.parse::<Decimal>().is_err(), "{} should be invalid", $s);)+
+            }
+        }
+    }
+
+    valid_theory!(valid_numbers, "0", "-0", "12345678901234567890123", "1.50", "-0.5e-3", "1E+400");
+    invalid_theory!(invalid_numbers, "", "-", "01", "1.", ".5", "1e", "+1", "1x", "0x10", "1.5.2");
+
+    #[test]
+    fn to_i64_checks_range() {
+        assert_eq!(d("-9223372036854775808").to_i64().unwrap(), i64::MIN);
+        assert!(d("9223372036854775808").to_i64().is_err());
+    }
+
+    #[test]
+    fn to_u64_checks_range() {
+        assert_eq!(d("18446744073709551615").to_u64().unwrap(), u64::MAX);
+        assert!(d("18446744073709551616").to_u64().is_err());
+        assert!(d("-1").to_u64().is_err());
+    }
+
+    #[test]
+    fn to_i128_handles_exponents_and_fractions() {
+        assert_eq!(d("1.50e2").to_i128().unwrap(), 150);
+        assert_eq!(d("1200e-2").to_i128().unwrap(), 12);
+        assert_eq!(d("0e999999").to_i128().unwrap(), 0);
+        assert_eq!(d("-170141183460469231731687303715884105728").to_i128().unwrap(), i128::MIN);
+        assert!(d("1.5").to_i128().is_err());
+        assert!(d("1e40").to_i128().is_err());
+        assert!(d("1e99999999999999999999").to_i128().is_err());
+        assert!(d("1e-99999999999999999999").to_i128().is_err());
+    }
+
+    #[test]
+    fn to_f64_rejects_infinity() {
+        assert_eq!(d("1.5").to_f64().unwrap(), 1.5);
+        assert!(d("1e400").to_f64().is_err());
+    }
+}
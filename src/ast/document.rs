@@ -0,0 +1,26 @@
+use super::{node::Node, visitor::ExpressionVisitor};
+
+/// A value with the whitespace and comments around it, as parsed by `parse_lossless`.
+#[derive(Debug)]
+pub struct Document {
+    pub leading: Vec<Node>,
+    pub value: Node,
+    pub trailing: Vec<Node>,
+}
+
+impl Document {
+    pub fn new(value: impl Into<Node>) -> Document {
+        Document { leading: Vec::new(), value: value.into(), trailing: Vec::new() }
+    }
+
+    /// Visits the leading trivia, the value and the trailing trivia in order.
+    pub fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
+        for e in self.leading.iter_mut() {
+            e.accept(visitor);
+        }
+        self.value.accept(visitor);
+        for e in self.trailing.iter_mut() {
+            e.accept(visitor);
+        }
+    }
+}
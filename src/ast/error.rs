@@ -0,0 +1,28 @@
+use super::{Expression, span::{Span, SpanShift}, visitor::ExpressionVisitor};
+use crate::parsing::errors::ParseError;
+
+/// Stands in for input that failed to parse when the parser recovers from errors.
+#[derive(Debug)]
+pub struct ErrorExpression {
+    pub error: ParseError,
+}
+
+impl ErrorExpression {
+    pub fn new(error: ParseError) -> ErrorExpression {
+        ErrorExpression { error }
+    }
+}
+
+impl Expression for ErrorExpression {
+    fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
+        visitor.visit_error(self);
+    }
+
+    fn span(&self) -> Span {
+        self.error.span()
+    }
+
+    /// Error nodes only come from recovering parses, which are not edited incrementally, so the
+    /// span stays where the error was found.
+    fn shift_spans(&mut self, _: &SpanShift) {}
+}
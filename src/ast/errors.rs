@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result};
+
+#[derive(Debug)]
+pub struct InvalidNumberError {
+    lexeme: String,
+}
+
+impl InvalidNumberError {
+    pub fn new(lexeme: &str) -> InvalidNumberError {
+        InvalidNumberError { lexeme: String::from(lexeme) }
+    }
+}
+
+impl Error for InvalidNumberError {}
+
+impl Display for InvalidNumberError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Invalid number {}", self.lexeme)
+    }
+}
+
+#[derive(Debug)]
+pub struct NumberConversionError {
+    lexeme: String,
+    target: String,
+    reason: String,
+}
+
+impl NumberConversionError {
+    pub fn new(lexeme: &str, target: &str, reason: &str) -> NumberConversionError {
+        NumberConversionError { lexeme: String::from(lexeme), target: String::from(target), reason: String::from(reason) }
+    }
+}
+
+impl Error for NumberConversionError {}
+
+impl Display for NumberConversionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Cannot convert {} to {}. {}", self.lexeme, self.target, self.reason)
+    }
+}
@@ -1,16 +1,33 @@
-use super::{Expression, visitor::ExpressionVisitor};
+use super::{Expression, node::Node, span::{Span, SpanShift}, visitor::ExpressionVisitor};
 
-#[derive(Debug)]
-pub struct JsonArrayExpression{}
+#[derive(Debug, Default)]
+pub struct JsonArrayExpression {
+    pub expressions: Vec<Node>,
+    pub span: Span,
+}
 
 impl JsonArrayExpression {
     pub fn new() -> JsonArrayExpression {
-        JsonArrayExpression{}
+        JsonArrayExpression { expressions: Vec::new(), span: Span::default() }
+    }
+    pub fn add_expr(&mut self, expr: impl Into<Node>) {
+        self.expressions.push(expr.into());
     }
 }
 
 impl Expression for JsonArrayExpression {
     fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
-        unimplemented!()
+        visitor.visit_array(self);
+    }
+
+    fn span(&self) -> Span {
+        self.span
     }
-}
\ No newline at end of file
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        self.span.shift(shift);
+        for e in self.expressions.iter_mut() {
+            e.shift_spans(shift);
+        }
+    }
+}
@@ -1,16 +1,17 @@
-use super::{Expression, visitor::ExpressionVisitor};
+use super::{Expression, node::Node, span::{Span, SpanShift}, visitor::ExpressionVisitor};
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct JsonObjectExpression {
-    pub expressions: Vec<Box<dyn Expression>>,
+    pub expressions: Vec<Node>,
+    pub span: Span,
 }
 
 impl JsonObjectExpression{
     pub fn new() -> JsonObjectExpression {
-        JsonObjectExpression{expressions: Vec::new() }
+        JsonObjectExpression { expressions: Vec::new(), span: Span::default() }
     }
-    pub fn add_expr(&mut self, expr: Box<dyn Expression>) {
-        self.expressions.push(expr);
+    pub fn add_expr(&mut self, expr: impl Into<Node>) {
+        self.expressions.push(expr.into());
     }
 }
 
@@ -18,10 +19,16 @@ impl Expression for JsonObjectExpression {
     fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
         visitor.visit_object(self);
     }
-}
 
-impl Expression for &mut JsonObjectExpression {
-    fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
-        visitor.visit_object(self);
+    fn span(&self) -> Span {
+        self.span
     }
-}
\ No newline at end of file
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        self.span.shift(shift);
+        for e in self.expressions.iter_mut() {
+            e.shift_spans(shift);
+        }
+    }
+}
+
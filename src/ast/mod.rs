@@ -1,31 +1,62 @@
 pub mod comma;
+pub mod comment;
+pub mod decimal;
+pub mod document;
+pub mod error;
+pub mod errors;
 pub mod json_array;
 pub mod json_object;
 pub mod name;
+pub mod node;
 pub mod property_assignment;
+pub mod span;
+pub mod template;
 pub mod value;
+pub mod visit;
 pub mod visitor;
 pub mod whitespace;
 use visitor::ExpressionVisitor;
+use decimal::Decimal;
+use span::{Span, SpanShift};
 
 pub trait Expression: std::fmt::Debug {
     fn accept(&mut self, visitor: &mut dyn ExpressionVisitor);
+    /// Where the expression was parsed from. Expressions built in code have an empty span.
+    fn span(&self) -> Span;
+    /// Moves the spans of the expression and everything in it to match an edit to the source.
+    fn shift_spans(&mut self, shift: &SpanShift);
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum JsonValue {
     Number(i64),
+    /// A number with a fraction or exponent. `lexeme` is the number as written, so `1.50` is
+    /// output as `1.50`.
+    Float { value: f64, lexeme: String },
+    /// Any number, kept as its validated text. Only produced when parsing with
+    /// `ParseOptions::arbitrary_precision`.
+    Decimal(Decimal),
     String(String),
     Boolean(bool),
-    // Object(JsonObjectExpression),
-    // Array(JsonArrayExpression)
+    Null,
+}
+
+impl JsonValue {
+    /// The exact value of a number, whichever way it was parsed. `None` for other values.
+    pub fn to_decimal(&self) -> Option<Decimal> {
+        match self {
+            JsonValue::Number(n) => Some(n.to_string().parse().expect("an i64 is a valid number")),
+            JsonValue::Float { lexeme, .. } => lexeme.parse().ok(),
+            JsonValue::Decimal(d) => Some(d.clone()),
+            _ => None,
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use super::visitor::{JsonExpressionVisitor, ExpressionVisitor};
-    use crate::parsing::ParseResult;
     use crate::ast::name::NameExpression;
     use crate::ast::value::ValueExpression;
 
@@ -52,21 +83,31 @@ mod tests {
 
     fn new_prop_assignment(name: &str, value: JsonValue) -> property_assignment::PropertyAssignmentExpression {
         property_assignment::PropertyAssignmentExpression::new(
-            Box::new(new_name_expr(name)),
-            Box::new(new_value_expr(value)))
+            new_name_expr(name),
+            new_value_expr(value))
     }
 
     expr_theory!(number_assignment, new_prop_assignment("foo", JsonValue::Number(1)),
         "\"foo\": 1");
     expr_theory!(string_assignment, new_prop_assignment("foo", JsonValue::String(String::from("bar"))),
         "\"foo\": \"bar\"");
+    expr_theory!(float_assignment, new_prop_assignment("foo", JsonValue::Float { value: 1.5, lexeme: String::from("1.50") }),
+        "\"foo\": 1.50");
+    expr_theory!(decimal_assignment, new_prop_assignment("foo", JsonValue::Decimal("123456789012345678901.50".parse().unwrap())),
+        "\"foo\": 123456789012345678901.50");
     expr_theory!(bool_assignment, new_prop_assignment("foo", JsonValue::Boolean(true)),
         "\"foo\": true");
 
+    expr_theory!(escaped_string_assignment, new_prop_assignment("a\"b", JsonValue::String(String::from("q\" \\ \n\t\u{1}é"))),
+        "\"a\\\"b\": \"q\\\" \\\\ \\n\\t\\u0001é\"");
+
     expr_theory!(comma_expression, comma::CommaExpression::new(), ",");
     expr_theory!(newline_expression, whitespace::WhitespaceExpression::new('\n'), "\n");
     expr_theory!(tab_expression, whitespace::WhitespaceExpression::new('\t'), "\t");
     expr_theory!(whitespace_expression, whitespace::WhitespaceExpression::new(' '), " ");
+    expr_theory!(whitespace_run_expression, whitespace::WhitespaceExpression::with_text(" \r\n\t"), " \r\n\t");
+    expr_theory!(comment_expression_is_not_json, comment::CommentExpression::new("// a"), "");
+    expr_theory!(trailing_comma_is_not_json, comma::CommaExpression { trailing: true, ..comma::CommaExpression::new() }, "");
 
     macro_rules! obj_expr_theory {
         ($name:ident, $expected:literal, $e:expr) => {
@@ -75,15 +116,10 @@ mod tests {
                 let mut v = JsonExpressionVisitor::new();
                 let mut obj_expr = json_object::JsonObjectExpression::new();
 
-                let nl: Box<dyn Expression> = Box::new(whitespace::WhitespaceExpression::new('\n'));
-                let tab: Box<dyn Expression> = Box::new(whitespace::WhitespaceExpression::new('\t'));
-                let assignment: Box<dyn Expression> = Box::new($e);
-                let nl2: Box<dyn Expression> = Box::new(whitespace::WhitespaceExpression::new('\n'));
-
-                obj_expr.add_expr(nl);
-                obj_expr.add_expr(tab);
-                obj_expr.add_expr(assignment);
-                obj_expr.add_expr(nl2);
+                obj_expr.add_expr(whitespace::WhitespaceExpression::new('\n'));
+                obj_expr.add_expr(whitespace::WhitespaceExpression::new('\t'));
+                obj_expr.add_expr($e);
+                obj_expr.add_expr(whitespace::WhitespaceExpression::new('\n'));
 
                 obj_expr.accept(&mut v);
 
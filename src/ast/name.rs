@@ -1,13 +1,17 @@
-use super::{Expression, visitor::ExpressionVisitor};
+use super::{Expression, span::{Span, SpanShift}, visitor::ExpressionVisitor};
 
 #[derive(Debug)]
 pub struct NameExpression {
-    pub name: String
+    pub name: String,
+    pub span: Span,
+    /// The name as written, quotes and escapes included, when parsed losslessly. Otherwise
+    /// empty.
+    pub raw: String,
 }
 
 impl NameExpression {
     pub fn new(name: String) -> NameExpression {
-        NameExpression { name }
+        NameExpression { name, span: Span::default(), raw: String::new() }
     }
 }
 
@@ -15,4 +19,12 @@ impl Expression for NameExpression {
     fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
         visitor.visit_name(self)
     }
-}
\ No newline at end of file
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        self.span.shift(shift);
+    }
+}
@@ -0,0 +1,106 @@
+use super::{
+    Expression,
+    comma::CommaExpression,
+    comment::CommentExpression,
+    error::ErrorExpression,
+    json_array::JsonArrayExpression,
+    json_object::JsonObjectExpression,
+    property_assignment::PropertyAssignmentExpression,
+    span::{Span, SpanShift},
+    template::TemplateExpression,
+    value::ValueExpression,
+    visitor::ExpressionVisitor,
+    whitespace::WhitespaceExpression};
+
+/// A node of the tree. Objects hold `Property` nodes and the commas and trivia between them,
+/// arrays hold values and the commas and trivia between them.
+#[derive(Debug)]
+pub enum Node {
+    Object(JsonObjectExpression),
+    Array(JsonArrayExpression),
+    Property(PropertyAssignmentExpression),
+    Value(ValueExpression),
+    Template(TemplateExpression),
+    Comma(CommaExpression),
+    Whitespace(WhitespaceExpression),
+    Comment(CommentExpression),
+    Error(ErrorExpression),
+}
+
+impl Node {
+    pub fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
+        match self {
+            Node::Object(e) => visitor.visit_object(e),
+            Node::Array(e) => visitor.visit_array(e),
+            Node::Property(e) => visitor.visit_property_assignment(e),
+            Node::Value(e) => visitor.visit_value(e),
+            Node::Template(e) => visitor.visit_template(e),
+            Node::Comma(e) => visitor.visit_comma_expression(e),
+            Node::Whitespace(e) => visitor.visit_whitespace_expression(e),
+            Node::Comment(e) => visitor.visit_comment(e),
+            Node::Error(e) => visitor.visit_error(e),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Node::Object(e) => e.span(),
+            Node::Array(e) => e.span(),
+            Node::Property(e) => e.span(),
+            Node::Value(e) => e.span(),
+            Node::Template(e) => e.span(),
+            Node::Comma(e) => e.span(),
+            Node::Whitespace(e) => e.span(),
+            Node::Comment(e) => e.span(),
+            Node::Error(e) => e.span(),
+        }
+    }
+
+    pub fn shift_spans(&mut self, shift: &SpanShift) {
+        match self {
+            Node::Object(e) => e.shift_spans(shift),
+            Node::Array(e) => e.shift_spans(shift),
+            Node::Property(e) => e.shift_spans(shift),
+            Node::Value(e) => e.shift_spans(shift),
+            Node::Template(e) => e.shift_spans(shift),
+            Node::Comma(e) => e.shift_spans(shift),
+            Node::Whitespace(e) => e.shift_spans(shift),
+            Node::Comment(e) => e.shift_spans(shift),
+            Node::Error(e) => e.shift_spans(shift),
+        }
+    }
+}
+
+impl Expression for Node {
+    fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
+        Node::accept(self, visitor);
+    }
+
+    fn span(&self) -> Span {
+        Node::span(self)
+    }
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        Node::shift_spans(self, shift);
+    }
+}
+
+macro_rules! node_from {
+    ($variant:ident, $t:ty) => {
+        impl From<$t> for Node {
+            fn from(e: $t) -> Node {
+                Node::$variant(e)
+            }
+        }
+    };
+}
+
+node_from!(Object, JsonObjectExpression);
+node_from!(Array, JsonArrayExpression);
+node_from!(Property, PropertyAssignmentExpression);
+node_from!(Value, ValueExpression);
+node_from!(Template, TemplateExpression);
+node_from!(Comma, CommaExpression);
+node_from!(Whitespace, WhitespaceExpression);
+node_from!(Comment, CommentExpression);
+node_from!(Error, ErrorExpression);
@@ -1,14 +1,18 @@
-use super::{Expression, JsonValue, name::NameExpression, visitor::ExpressionVisitor};
+use super::{Expression, name::NameExpression, node::Node, span::{Span, SpanShift}, visitor::ExpressionVisitor};
 
 #[derive(Debug)]
 pub struct PropertyAssignmentExpression {
-    pub name: Box<dyn Expression>,
-    pub value: Box<dyn Expression>,
+    pub name: NameExpression,
+    /// Whitespace and comments between the name and the colon, kept when parsing losslessly.
+    pub before_colon: Vec<Node>,
+    /// Whitespace and comments between the colon and the value, kept when parsing losslessly.
+    pub after_colon: Vec<Node>,
+    pub value: Box<Node>,
 }
 
 impl PropertyAssignmentExpression {
-    pub fn new(name: Box<dyn Expression>, value: Box<dyn Expression>) -> PropertyAssignmentExpression {
-        PropertyAssignmentExpression { name, value }
+    pub fn new(name: NameExpression, value: impl Into<Node>) -> PropertyAssignmentExpression {
+        PropertyAssignmentExpression { name, before_colon: Vec::new(), after_colon: Vec::new(), value: Box::new(value.into()) }
     }
 }
 
@@ -16,4 +20,16 @@ impl Expression for PropertyAssignmentExpression {
     fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
         visitor.visit_property_assignment(self);
     }
+
+    fn span(&self) -> Span {
+        self.name.span().to(self.value.span())
+    }
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        self.name.shift_spans(shift);
+        for e in self.before_colon.iter_mut().chain(self.after_colon.iter_mut()) {
+            e.shift_spans(shift);
+        }
+        self.value.shift_spans(shift);
+    }
 }
\ No newline at end of file
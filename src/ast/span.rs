@@ -0,0 +1,89 @@
+/// A point in the source text. `line` and `column` are 1-based and count characters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// The position just after `c`, which starts at this position.
+    pub fn after(self, c: char) -> Position {
+        match c {
+            '\n' => Position { offset: self.offset + 1, line: self.line + 1, column: 1 },
+            c => Position { offset: self.offset + c.len_utf8(), line: self.line, column: self.column + 1 },
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Position {
+        Position { offset: 0, line: 1, column: 1 }
+    }
+}
+
+/// A byte range in the source text, `start` inclusive and `end` exclusive, with the line and
+/// column it starts at.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: Position, end: usize) -> Span {
+        Span { start: start.offset, end, line: start.line, column: start.column }
+    }
+
+    /// Where the span starts.
+    pub fn start_position(self) -> Position {
+        Position { offset: self.start, line: self.line, column: self.column }
+    }
+
+    /// From the start of this span to the end of `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span { end: other.end, ..self }
+    }
+
+    /// Moves the span to where its text is after an edit. A span that starts before the edit
+    /// and ends after it grows or shrinks with it.
+    pub fn shift(&mut self, shift: &SpanShift) {
+        if self.start >= shift.old_end {
+            if self.line == shift.old_end_line {
+                self.column = self.column.wrapping_add_signed(shift.columns);
+            }
+            self.line = self.line.wrapping_add_signed(shift.lines);
+            self.start = self.start.wrapping_add_signed(shift.offset);
+            self.end = self.end.wrapping_add_signed(shift.offset);
+        } else if self.end >= shift.old_end {
+            self.end = self.end.wrapping_add_signed(shift.offset);
+        }
+    }
+}
+
+/// How an edit moves the text after it. Text from `old_end` on moves by `offset` bytes and
+/// `lines` lines, and on the line the edit ended on, by `columns` columns.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpanShift {
+    pub old_end: usize,
+    pub old_end_line: usize,
+    pub offset: isize,
+    pub lines: isize,
+    pub columns: isize,
+}
+
+impl SpanShift {
+    /// The shift for an edit whose replaced text ended at `old_end` and whose new text ends at
+    /// `new_end`.
+    pub fn new(old_end: Position, new_end: Position) -> SpanShift {
+        SpanShift {
+            old_end: old_end.offset,
+            old_end_line: old_end.line,
+            offset: new_end.offset as isize - old_end.offset as isize,
+            lines: new_end.line as isize - old_end.line as isize,
+            columns: new_end.column as isize - old_end.column as isize,
+        }
+    }
+}
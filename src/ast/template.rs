@@ -0,0 +1,31 @@
+use super::{Expression, span::{Span, SpanShift}, visitor::ExpressionVisitor};
+use crate::tags::Segment;
+
+/// A string containing tags, such as `"id-{{guid()}}"`. Strings without tags are values.
+#[derive(Debug)]
+pub struct TemplateExpression {
+    pub segments: Vec<Segment>,
+    pub span: Span,
+    /// The string as written, quotes included, when parsed losslessly. Otherwise empty.
+    pub raw: String,
+}
+
+impl TemplateExpression {
+    pub fn new(segments: Vec<Segment>) -> TemplateExpression {
+        TemplateExpression { segments, span: Span::default(), raw: String::new() }
+    }
+}
+
+impl Expression for TemplateExpression {
+    fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
+        visitor.visit_template(self)
+    }
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        self.span.shift(shift);
+    }
+}
@@ -1,13 +1,22 @@
-use super::{Expression, JsonValue, visitor::ExpressionVisitor};
+use super::{Expression, JsonValue, span::{Span, SpanShift}, visitor::ExpressionVisitor};
 
 #[derive(Debug)]
 pub struct ValueExpression {
-    pub value: JsonValue
+    pub value: JsonValue,
+    pub span: Span,
+    /// The value as written when parsed losslessly. Otherwise empty.
+    pub raw: String,
 }
 
 impl ValueExpression {
     pub fn new(value: JsonValue) -> ValueExpression {
-        ValueExpression {value}
+        ValueExpression { value, span: Span::default(), raw: String::new() }
+    }
+
+    /// Replaces the value, which is then written from `value` rather than as it was parsed.
+    pub fn set_value(&mut self, value: JsonValue) {
+        self.value = value;
+        self.raw.clear();
     }
 }
 
@@ -15,4 +24,12 @@ impl Expression for ValueExpression {
     fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
         visitor.visit_value(self)
     }
-}
\ No newline at end of file
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        self.span.shift(shift);
+    }
+}
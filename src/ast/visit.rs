@@ -0,0 +1,211 @@
+use super::{
+    comma::CommaExpression,
+    comment::CommentExpression,
+    document::Document,
+    error::ErrorExpression,
+    json_array::JsonArrayExpression,
+    json_object::JsonObjectExpression,
+    name::NameExpression,
+    node::Node,
+    property_assignment::PropertyAssignmentExpression,
+    template::TemplateExpression,
+    value::ValueExpression,
+    whitespace::WhitespaceExpression};
+
+/// Visits a tree, returning a `T` from each node. Every method has a default: objects, arrays,
+/// members and documents walk their children, combining what each returns, and everything
+/// else returns `T::default()`. A visitor only overrides what it cares about, calling the
+/// matching `walk_*` function to keep walking below it.
+pub trait Visitor<T: Default = ()> {
+    /// Combines what two siblings returned, the first before the second. Keeps the second by
+    /// default.
+    fn combine(&mut self, _first: T, second: T) -> T {
+        second
+    }
+
+    fn visit_node(&mut self, node: &Node) -> T {
+        walk_node(self, node)
+    }
+
+    fn visit_document(&mut self, document: &Document) -> T {
+        walk_document(self, document)
+    }
+
+    fn visit_object(&mut self, expr: &JsonObjectExpression) -> T {
+        walk_object(self, expr)
+    }
+
+    fn visit_array(&mut self, expr: &JsonArrayExpression) -> T {
+        walk_array(self, expr)
+    }
+
+    fn visit_property(&mut self, expr: &PropertyAssignmentExpression) -> T {
+        walk_property(self, expr)
+    }
+
+    fn visit_name(&mut self, _: &NameExpression) -> T {
+        T::default()
+    }
+
+    fn visit_value(&mut self, _: &ValueExpression) -> T {
+        T::default()
+    }
+
+    fn visit_template(&mut self, _: &TemplateExpression) -> T {
+        T::default()
+    }
+
+    fn visit_comma(&mut self, _: &CommaExpression) -> T {
+        T::default()
+    }
+
+    fn visit_whitespace(&mut self, _: &WhitespaceExpression) -> T {
+        T::default()
+    }
+
+    fn visit_comment(&mut self, _: &CommentExpression) -> T {
+        T::default()
+    }
+
+    fn visit_error(&mut self, _: &ErrorExpression) -> T {
+        T::default()
+    }
+}
+
+/// Calls the visitor method for the kind of node.
+pub fn walk_node<T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, node: &Node) -> T {
+    match node {
+        Node::Object(e) => visitor.visit_object(e),
+        Node::Array(e) => visitor.visit_array(e),
+        Node::Property(e) => visitor.visit_property(e),
+        Node::Value(e) => visitor.visit_value(e),
+        Node::Template(e) => visitor.visit_template(e),
+        Node::Comma(e) => visitor.visit_comma(e),
+        Node::Whitespace(e) => visitor.visit_whitespace(e),
+        Node::Comment(e) => visitor.visit_comment(e),
+        Node::Error(e) => visitor.visit_error(e),
+    }
+}
+
+/// Visits the leading trivia, the value and the trailing trivia in order.
+pub fn walk_document<T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, document: &Document) -> T {
+    walk_nodes(visitor, document.leading.iter().chain(Some(&document.value)).chain(document.trailing.iter()))
+}
+
+pub fn walk_object<T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, expr: &JsonObjectExpression) -> T {
+    walk_nodes(visitor, expr.expressions.iter())
+}
+
+pub fn walk_array<T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, expr: &JsonArrayExpression) -> T {
+    walk_nodes(visitor, expr.expressions.iter())
+}
+
+/// Visits the name, the trivia around the colon and the value in order.
+pub fn walk_property<T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, expr: &PropertyAssignmentExpression) -> T {
+    let name = visitor.visit_name(&expr.name);
+    let rest = expr.before_colon.iter().chain(expr.after_colon.iter()).chain(Some(expr.value.as_ref()));
+
+    rest.fold(name, |result, node| {
+        let next = visitor.visit_node(node);
+        visitor.combine(result, next)
+    })
+}
+
+fn walk_nodes<'a, T: Default, V: Visitor<T> + ?Sized>(visitor: &mut V, nodes: impl Iterator<Item = &'a Node>) -> T {
+    nodes.fold(T::default(), |result, node| {
+        let next = visitor.visit_node(node);
+        visitor.combine(result, next)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::JsonValue;
+    use crate::parsing::{ParseOptions, parse, parse_lossless};
+    use crate::tags::Segment;
+
+    /// Only overrides `visit_name`, keeping state rather than returning it.
+    #[derive(Default)]
+    struct Keys {
+        keys: Vec<String>,
+    }
+
+    impl Visitor for Keys {
+        fn visit_name(&mut self, expr: &NameExpression) {
+            self.keys.push(expr.name.clone());
+        }
+    }
+
+    struct TagCount;
+
+    impl Visitor<usize> for TagCount {
+        fn combine(&mut self, first: usize, second: usize) -> usize {
+            first + second
+        }
+
+        fn visit_template(&mut self, expr: &TemplateExpression) -> usize {
+            expr.segments.iter().filter(|s| !matches!(s, Segment::Text(_))).count()
+        }
+    }
+
+    /// Stops walking at arrays, so only finds numbers outside of them.
+    struct OutsideArrays;
+
+    impl Visitor<Vec<i64>> for OutsideArrays {
+        fn combine(&mut self, mut first: Vec<i64>, second: Vec<i64>) -> Vec<i64> {
+            first.extend(second);
+            first
+        }
+
+        fn visit_array(&mut self, _: &JsonArrayExpression) -> Vec<i64> {
+            Vec::new()
+        }
+
+        fn visit_value(&mut self, expr: &ValueExpression) -> Vec<i64> {
+            match expr.value {
+                JsonValue::Number(n) => vec![n],
+                _ => Vec::new(),
+            }
+        }
+    }
+
+    const TEMPLATE: &str = r#"{"a": 1, "b": {"c": "{{guid}}-{{$n}}", "d": [2, {"e": "{{pick(1, 2)}}"}]}, "f": 3}"#;
+
+    #[test]
+    fn visitor_collects_names() {
+        let mut keys = Keys::default();
+        keys.visit_node(&parse(TEMPLATE).unwrap());
+
+        assert_eq!(keys.keys, vec!["a", "b", "c", "d", "e", "f"]);
+    }
+
+    #[test]
+    fn visitor_counts_tags() {
+        assert_eq!(TagCount.visit_node(&parse(TEMPLATE).unwrap()), 3);
+    }
+
+    #[test]
+    fn visitor_can_stop_walking() {
+        assert_eq!(OutsideArrays.visit_node(&parse(TEMPLATE).unwrap()), vec![1, 3]);
+    }
+
+    #[test]
+    fn visitor_walks_documents() {
+        #[derive(Default)]
+        struct Comments(usize);
+
+        impl Visitor for Comments {
+            fn visit_comment(&mut self, _: &CommentExpression) {
+                self.0 += 1;
+            }
+        }
+
+        let options = ParseOptions { lenient: true, ..ParseOptions::default() };
+        let document = parse_lossless("// a\n{b: /* c */ 1, // d\n}\n/* e */", &options).unwrap();
+        let mut comments = Comments::default();
+        comments.visit_document(&document);
+
+        assert_eq!(comments.0, 4);
+    }
+}
@@ -1,10 +1,15 @@
 use super::{
+    Expression,
     JsonValue,
     comma::CommaExpression,
+    comment::CommentExpression,
+    error::ErrorExpression,
     json_object::JsonObjectExpression,
     name::NameExpression,
     property_assignment::PropertyAssignmentExpression,
+    template::TemplateExpression,
     whitespace::WhitespaceExpression};
+use crate::tags::Segment;
 use crate::ast::json_array::JsonArrayExpression;
 use crate::ast::value::ValueExpression;
 
@@ -16,17 +21,24 @@ pub trait ExpressionVisitor {
     fn visit_property_assignment(&mut self, expr: &mut PropertyAssignmentExpression);
     fn visit_comma_expression(&mut self, expr: &CommaExpression);
     fn visit_value(&mut self, expr: &mut ValueExpression);
+    fn visit_template(&mut self, expr: &mut TemplateExpression);
     fn visit_whitespace_expression(&mut self, expr: &WhitespaceExpression);
+    fn visit_comment(&mut self, expr: &CommentExpression);
+    fn visit_error(&mut self, expr: &ErrorExpression);
 
-    fn get_json(&self) -> &str;
+    /// The JSON written, for visitors that write it.
+    fn get_json(&self) -> &str {
+        ""
+    }
 }
 
+#[derive(Default)]
 pub struct JsonExpressionVisitor {
     pub json: String
 }
 
 impl JsonExpressionVisitor {
-    pub fn new() -> impl ExpressionVisitor {
+    pub fn new() -> JsonExpressionVisitor {
         JsonExpressionVisitor { json: String::new() }
     }
 }
@@ -35,7 +47,9 @@ impl ExpressionVisitor for JsonExpressionVisitor {
     fn visit_array(&mut self, expr: &mut JsonArrayExpression) {
         self.json = format!("{}[", self.json);
 
-        // TODO: Implement
+        for e in expr.expressions.iter_mut() {
+            e.accept(self);
+        }
 
         self.json = format!("{}]", self.json);
     }
@@ -51,7 +65,7 @@ impl ExpressionVisitor for JsonExpressionVisitor {
     }
 
     fn visit_name(&mut self, expr: &mut NameExpression) {
-        self.json = format!("{}\"{}\"", self.json, expr.name);
+        write_template_string(&expr.name, &mut self.json);
     }
 
     fn visit_property_assignment(&mut self, expr: &mut PropertyAssignmentExpression) {
@@ -60,23 +74,183 @@ impl ExpressionVisitor for JsonExpressionVisitor {
         expr.value.accept(self);
     }
 
-    fn visit_comma_expression(&mut self, _: &CommaExpression) {
-        self.json = format!("{},", self.json);
+    /// A trailing comma is not valid JSON.
+    fn visit_comma_expression(&mut self, expr: &CommaExpression) {
+        if !expr.trailing {
+            self.json = format!("{},", self.json);
+        }
     }
 
     fn visit_value(&mut self, expr: &mut ValueExpression) {
-        self.json = match &expr.value {
-            JsonValue::String(s) => format!("{}\"{}\"", self.json, *s),
-            JsonValue::Number(d) => format!("{}{}", self.json, *d),
-            JsonValue::Boolean(b) => format!("{}{}", self.json, *b)
-        }
+        write_template_value(&expr.value, &mut self.json);
+    }
+
+    fn visit_template(&mut self, expr: &mut TemplateExpression) {
+        write_template(expr, &mut self.json);
     }
 
     fn visit_whitespace_expression(&mut self, e: &WhitespaceExpression) {
-        self.json = format!("{}{}", self.json, e.get_char());
+        self.json = format!("{}{}", self.json, e.get_text());
     }
 
+    /// JSON has no comments.
+    fn visit_comment(&mut self, _: &CommentExpression) {}
+
+    /// Input that failed to parse has no JSON.
+    fn visit_error(&mut self, _: &ErrorExpression) {}
+
     fn get_json(&self) -> &str {
         &self.json
     }
-}
\ No newline at end of file
+}
+
+/// Writes a tree back as it was parsed by `parse_lossless`, byte for byte. Nodes built or changed
+/// in code are written the way `JsonExpressionVisitor` writes them.
+#[derive(Default)]
+pub struct SourceExpressionVisitor {
+    pub source: String
+}
+
+impl SourceExpressionVisitor {
+    pub fn new() -> SourceExpressionVisitor {
+        SourceExpressionVisitor { source: String::new() }
+    }
+}
+
+impl ExpressionVisitor for SourceExpressionVisitor {
+    fn visit_array(&mut self, expr: &mut JsonArrayExpression) {
+        self.source.push('[');
+        for e in expr.expressions.iter_mut() {
+            e.accept(self);
+        }
+        self.source.push(']');
+    }
+
+    fn visit_object(&mut self, expr: &mut JsonObjectExpression) {
+        self.source.push('{');
+        for e in expr.expressions.iter_mut() {
+            e.accept(self);
+        }
+        self.source.push('}');
+    }
+
+    fn visit_name(&mut self, expr: &mut NameExpression) {
+        match expr.raw.as_str() {
+            "" => write_template_string(&expr.name, &mut self.source),
+            raw => self.source.push_str(raw),
+        }
+    }
+
+    fn visit_property_assignment(&mut self, expr: &mut PropertyAssignmentExpression) {
+        expr.name.accept(self);
+        for e in expr.before_colon.iter_mut() {
+            e.accept(self);
+        }
+        self.source.push(':');
+        for e in expr.after_colon.iter_mut() {
+            e.accept(self);
+        }
+        expr.value.accept(self);
+    }
+
+    fn visit_comma_expression(&mut self, _: &CommaExpression) {
+        self.source.push(',');
+    }
+
+    fn visit_value(&mut self, expr: &mut ValueExpression) {
+        match expr.raw.as_str() {
+            "" => write_template_value(&expr.value, &mut self.source),
+            raw => self.source.push_str(raw),
+        }
+    }
+
+    fn visit_template(&mut self, expr: &mut TemplateExpression) {
+        match expr.raw.as_str() {
+            "" => write_template(expr, &mut self.source),
+            raw => self.source.push_str(raw),
+        }
+    }
+
+    fn visit_whitespace_expression(&mut self, expr: &WhitespaceExpression) {
+        self.source.push_str(expr.get_text());
+    }
+
+    fn visit_comment(&mut self, expr: &CommentExpression) {
+        self.source.push_str(&expr.text);
+    }
+
+    /// Lossless parsing does not recover, so there are no errors to write.
+    fn visit_error(&mut self, _: &ErrorExpression) {}
+
+    fn get_json(&self) -> &str {
+        &self.source
+    }
+}
+
+/// Writes the template back as a string, escaping any `{{` in its text as `\{{`.
+fn write_template(expr: &TemplateExpression, json: &mut String) {
+    json.push('"');
+    for segment in expr.segments.iter() {
+        match segment {
+            Segment::Text(text) => write_escaped(text, true, json),
+            Segment::Tag(tag) => write_escaped(&format!("{{{{{}}}}}", tag), false, json),
+            Segment::Variable(name) => write_escaped(&format!("{{{{${}}}}}", name), false, json),
+        }
+    }
+    json.push('"');
+}
+
+/// Writes a value into a template, where strings must not start tags.
+fn write_template_value(value: &JsonValue, json: &mut String) {
+    match value {
+        JsonValue::String(s) => write_template_string(s, json),
+        v => write_json_value(v, json),
+    }
+}
+
+pub fn write_json_value(value: &JsonValue, json: &mut String) {
+    match value {
+        JsonValue::String(s) => write_json_string(s, json),
+        JsonValue::Number(n) => json.push_str(&n.to_string()),
+        JsonValue::Float { lexeme, .. } => json.push_str(lexeme),
+        JsonValue::Decimal(d) => json.push_str(d.as_str()),
+        JsonValue::Boolean(b) => json.push_str(&b.to_string()),
+        JsonValue::Null => json.push_str("null"),
+    }
+}
+
+/// Appends `s` to `json` as a quoted JSON string, escaping quotes, backslashes and control
+/// characters.
+pub fn write_json_string(s: &str, json: &mut String) {
+    json.push('"');
+    write_escaped(s, false, json);
+    json.push('"');
+}
+
+/// Appends a quoted string to a template, escaping any `{{` so that it does not start a tag when
+/// the template is parsed again.
+fn write_template_string(s: &str, json: &mut String) {
+    json.push('"');
+    write_escaped(s, true, json);
+    json.push('"');
+}
+
+/// Appends the escaped contents of a string. With `braces` a `{` followed by another is escaped
+/// so that it does not start a tag.
+fn write_escaped(s: &str, braces: bool, json: &mut String) {
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if braces && chars.peek() == Some(&'{') => json.push_str("\\{"),
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\u{8}' => json.push_str("\\b"),
+            '\u{c}' => json.push_str("\\f"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < '\u{20}' => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+}
@@ -1,17 +1,23 @@
-use super::{Expression, visitor::ExpressionVisitor};
+use super::{Expression, span::{Span, SpanShift}, visitor::ExpressionVisitor};
 
+/// A run of whitespace between tokens, kept when parsing losslessly.
 #[derive(Debug)]
 pub struct WhitespaceExpression {
-    c: char
+    text: String,
+    pub span: Span,
 }
 
 impl WhitespaceExpression {
     pub fn new(c: char) -> WhitespaceExpression {
-        WhitespaceExpression{c}
+        WhitespaceExpression::with_text(&c.to_string())
     }
 
-    pub fn get_char(&self) -> char {
-        self.c
+    pub fn with_text(text: &str) -> WhitespaceExpression {
+        WhitespaceExpression { text: String::from(text), span: Span::default() }
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
     }
 }
 
@@ -19,4 +25,12 @@ impl Expression for WhitespaceExpression {
     fn accept(&mut self, visitor: &mut dyn ExpressionVisitor) {
         visitor.visit_whitespace_expression(self);
     }
-}
\ No newline at end of file
+
+    fn span(&self) -> Span {
+        self.span
+    }
+
+    fn shift_spans(&mut self, shift: &SpanShift) {
+        self.span.shift(shift);
+    }
+}
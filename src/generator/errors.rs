@@ -0,0 +1,124 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub struct UndefinedVariableError {
+    name: String,
+}
+
+impl UndefinedVariableError {
+    pub fn new(name: &str) -> UndefinedVariableError {
+        UndefinedVariableError { name: String::from(name) }
+    }
+}
+
+impl Error for UndefinedVariableError {}
+
+impl Display for UndefinedVariableError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Undefined variable ${}", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct UndefinedDefError {
+    name: String,
+}
+
+impl UndefinedDefError {
+    pub fn new(name: &str) -> UndefinedDefError {
+        UndefinedDefError { name: String::from(name) }
+    }
+}
+
+impl Error for UndefinedDefError {}
+
+impl Display for UndefinedDefError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Undefined sub-template {}", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct RecursiveDefError {
+    name: String,
+}
+
+impl RecursiveDefError {
+    pub fn new(name: &str) -> RecursiveDefError {
+        RecursiveDefError { name: String::from(name) }
+    }
+}
+
+impl Error for RecursiveDefError {}
+
+impl Display for RecursiveDefError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Sub-template {} uses itself", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidSectionError {
+    section: String,
+    message: String,
+}
+
+impl InvalidSectionError {
+    pub fn new(section: &str, message: &str) -> InvalidSectionError {
+        InvalidSectionError { section: String::from(section), message: String::from(message) }
+    }
+}
+
+impl Error for InvalidSectionError {}
+
+impl Display for InvalidSectionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Invalid {} section. {}", self.section, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub struct IncludeError {
+    path: PathBuf,
+    source: Box<dyn Error>,
+}
+
+impl IncludeError {
+    pub fn new(path: &Path, source: Box<dyn Error>) -> IncludeError {
+        IncludeError { path: path.to_path_buf(), source }
+    }
+}
+
+impl Error for IncludeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+impl Display for IncludeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}. In {}", self.source, self.path.display())
+    }
+}
+
+#[derive(Debug)]
+pub struct IncludeCycleError {
+    chain: Vec<PathBuf>,
+}
+
+impl IncludeCycleError {
+    pub fn new(chain: Vec<PathBuf>) -> IncludeCycleError {
+        IncludeCycleError { chain }
+    }
+}
+
+impl Error for IncludeCycleError {}
+
+impl Display for IncludeCycleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let chain: Vec<String> = self.chain.iter().map(|p| p.display().to_string()).collect();
+        write!(f, "Include cycle {}", chain.join(" -> "))
+    }
+}
@@ -0,0 +1,536 @@
+pub mod errors;
+pub mod template;
+
+use crate::ast::{Expression, JsonValue, visitor::{write_json_string, write_json_value}};
+use crate::parsing::ParseOptions;
+use crate::tags::{Segment, Tag, TagArg, TagContext, errors::TagArgumentError};
+use errors::{InvalidSectionError, RecursiveDefError, UndefinedDefError, UndefinedVariableError};
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use template::{Template, TemplateBuilder, TemplateWarning, build_file};
+
+/// Top-level section declaring variables that are evaluated once per document.
+pub const VARS_SECTION: &str = "$vars";
+/// Top-level section declaring named sub-templates that `{{use("name", ...)}}` instantiates.
+pub const DEFS_SECTION: &str = "$defs";
+/// Property of a sub-template naming its parameters, separated by commas or spaces, e.g.
+/// `"$params": "min max"`.
+pub const PARAMS_PROPERTY: &str = "$params";
+pub const USE_TAG: &str = "use";
+
+#[derive(Clone, Debug, Default)]
+pub struct GenerateOptions {
+    /// Directory that `{{pick("name")}}` resolves dictionaries from.
+    pub dictionary_dir: Option<PathBuf>,
+    /// Seed for reproducible output. Entropy is used when not set.
+    pub seed: Option<u64>,
+    /// Locale for the built-in name, address and phone data, e.g. `de_DE`. Defaults to `en_US`.
+    pub locale: Option<String>,
+    /// Options for parsing the template and any files it includes.
+    pub parse_options: ParseOptions,
+    /// Directory that `{{include("file")}}` paths in a template not read from a file are resolved
+    /// against. Defaults to the current directory.
+    pub base_dir: Option<PathBuf>,
+}
+
+/// A value produced by evaluating a `Template`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GeneratedValue {
+    Object(Vec<(String, GeneratedValue)>),
+    Array(Vec<GeneratedValue>),
+    Value(JsonValue),
+}
+
+impl GeneratedValue {
+    pub fn write_json(&self, json: &mut String) {
+        match self {
+            GeneratedValue::Object(props) => {
+                json.push('{');
+                for (i, (name, value)) in props.iter().enumerate() {
+                    if i > 0 {
+                        json.push(',');
+                    }
+                    write_json_string(name, json);
+                    json.push(':');
+                    value.write_json(json);
+                }
+                json.push('}');
+            },
+            GeneratedValue::Array(items) => {
+                json.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        json.push(',');
+                    }
+                    item.write_json(json);
+                }
+                json.push(']');
+            },
+            GeneratedValue::Value(v) => write_json_value(v, json),
+        }
+    }
+
+    /// The text used when the value is interpolated into a string.
+    fn write_text(&self, s: &mut String) {
+        match self {
+            GeneratedValue::Value(JsonValue::String(v)) => s.push_str(v),
+            GeneratedValue::Value(JsonValue::Number(n)) => s.push_str(&n.to_string()),
+            GeneratedValue::Value(JsonValue::Float { lexeme, .. }) => s.push_str(lexeme),
+            GeneratedValue::Value(JsonValue::Decimal(d)) => s.push_str(d.as_str()),
+            GeneratedValue::Value(JsonValue::Boolean(b)) => s.push_str(&b.to_string()),
+            v => v.write_json(s),
+        }
+    }
+}
+
+pub fn generate(expr: &mut dyn Expression, options: &GenerateOptions) -> Result<String, Box<dyn Error>> {
+    generate_with_warnings(expr, options).map(|(json, _)| json)
+}
+
+/// Generates from a parsed template, also returning the warnings from any files it includes.
+pub fn generate_with_warnings(expr: &mut dyn Expression, options: &GenerateOptions) -> Result<(String, Vec<TemplateWarning>), Box<dyn Error>> {
+    let mut builder = TemplateBuilder::new(options.base_dir.as_deref().unwrap_or_else(|| Path::new(".")), &options.parse_options);
+    expr.accept(&mut builder);
+
+    let (template, warnings) = builder.build()?;
+    Ok((generate_template(template, options)?, warnings))
+}
+
+/// Generates from the template in `path`, resolving includes relative to it.
+pub fn generate_file(path: &Path, options: &GenerateOptions) -> Result<String, Box<dyn Error>> {
+    generate_file_with_warnings(path, options).map(|(json, _)| json)
+}
+
+/// Generates from the template in `path`, also returning the warnings from it and any files it
+/// includes.
+pub fn generate_file_with_warnings(path: &Path, options: &GenerateOptions) -> Result<(String, Vec<TemplateWarning>), Box<dyn Error>> {
+    let (template, warnings) = build_file(path, &[], &options.parse_options)?;
+    Ok((generate_template(template, options)?, warnings))
+}
+
+fn generate_template(template: Template, options: &GenerateOptions) -> Result<String, Box<dyn Error>> {
+    let mut json = String::new();
+    Generator::new(options).generate(template)?.write_json(&mut json);
+
+    Ok(json)
+}
+
+/// A named sub-template from the `$defs` section.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Def {
+    pub params: Vec<String>,
+    pub body: Template,
+}
+
+pub struct Generator {
+    tags: TagContext,
+    vars: HashMap<String, GeneratedValue>,
+    defs: HashMap<String, Def>,
+    /// Parameters of the sub-templates currently being instantiated, innermost last.
+    scopes: Vec<(String, HashMap<String, GeneratedValue>)>,
+}
+
+impl Generator {
+    pub fn new(options: &GenerateOptions) -> Generator {
+        Generator { tags: TagContext::new(options), vars: HashMap::new(), defs: HashMap::new(), scopes: Vec::new() }
+    }
+
+    /// Generates one document, first reading its `$defs` and evaluating its `$vars` sections.
+    pub fn generate(&mut self, template: Template) -> Result<GeneratedValue, Box<dyn Error>> {
+        self.vars.clear();
+        self.defs.clear();
+
+        let template = match template {
+            Template::Object(props) => {
+                let mut body = Vec::with_capacity(props.len());
+                let mut vars = Vec::new();
+                for (name, value) in props {
+                    match name.as_str() {
+                        DEFS_SECTION => self.define_defs(value)?,
+                        VARS_SECTION => vars.push(value),
+                        _ => body.push((name, value)),
+                    }
+                }
+
+                // Defs are read first so variables can be declared with `use`.
+                for section in vars {
+                    self.define_vars(section)?;
+                }
+
+                Template::Object(body)
+            },
+            t => t,
+        };
+
+        self.evaluate(&template)
+    }
+
+    fn define_defs(&mut self, section: Template) -> Result<(), Box<dyn Error>> {
+        let defs = match section {
+            Template::Object(defs) => defs,
+            _ => return Err(Box::new(InvalidSectionError::new(DEFS_SECTION, "Expected an object"))),
+        };
+
+        for (name, body) in defs {
+            let def = match body {
+                Template::Object(props) => {
+                    let (params, props): (Vec<_>, Vec<_>) = props.into_iter()
+                        .partition(|(n, _)| n == PARAMS_PROPERTY);
+
+                    let params = match params.as_slice() {
+                        [] => Vec::new(),
+                        [(_, Template::Value(JsonValue::String(p)))] => parse_params(p)
+                            .ok_or_else(|| InvalidSectionError::new(DEFS_SECTION, &format!("Invalid {} in {}", PARAMS_PROPERTY, name)))?,
+                        _ => return Err(Box::new(InvalidSectionError::new(DEFS_SECTION,
+                            &format!("{} in {} must be a single string", PARAMS_PROPERTY, name)))),
+                    };
+
+                    Def { params, body: Template::Object(props) }
+                },
+                body => Def { params: Vec::new(), body },
+            };
+
+            self.defs.insert(name, def);
+        }
+
+        Ok(())
+    }
+
+    fn define_vars(&mut self, section: Template) -> Result<(), Box<dyn Error>> {
+        let vars = match section {
+            Template::Object(vars) => vars,
+            _ => return Err(Box::new(InvalidSectionError::new(VARS_SECTION, "Expected an object"))),
+        };
+
+        // Evaluated in order so a variable can refer to the ones declared before it.
+        for (name, template) in vars {
+            let value = self.evaluate(&template)?;
+            self.vars.insert(name, value);
+        }
+
+        Ok(())
+    }
+
+    pub fn evaluate(&mut self, template: &Template) -> Result<GeneratedValue, Box<dyn Error>> {
+        Ok(match template {
+            Template::Object(props) => {
+                let mut values = Vec::with_capacity(props.len());
+                for (name, value) in props {
+                    values.push((name.clone(), self.evaluate(value)?));
+                }
+                GeneratedValue::Object(values)
+            },
+            Template::Array(items) => {
+                let mut values = Vec::with_capacity(items.len());
+                for item in items {
+                    values.push(self.evaluate(item)?);
+                }
+                GeneratedValue::Array(values)
+            },
+            Template::Value(v) => GeneratedValue::Value(v.clone()),
+            Template::Tag(t) => self.evaluate_tag(t)?,
+            Template::Variable(name) => self.variable(name)?.clone(),
+            Template::Interpolated(segments) => {
+                let mut s = String::new();
+                for segment in segments {
+                    match segment {
+                        Segment::Text(text) => s.push_str(text),
+                        Segment::Tag(t) => self.evaluate_tag(t)?.write_text(&mut s),
+                        Segment::Variable(name) => self.variable(name)?.write_text(&mut s),
+                    }
+                }
+                GeneratedValue::Value(JsonValue::String(s))
+            },
+        })
+    }
+
+    fn evaluate_tag(&mut self, tag: &Tag) -> Result<GeneratedValue, Box<dyn Error>> {
+        let tag = tag.bind(|name| match self.variable(name)? {
+            GeneratedValue::Value(v) => Ok(v.clone()),
+            _ => Err(Box::new(TagArgumentError::new(&tag.name, &format!("${} is not a scalar value", name)))),
+        })?;
+
+        if tag.name == USE_TAG {
+            self.use_def(&tag)
+        } else {
+            Ok(GeneratedValue::Value(self.tags.evaluate(&tag)?))
+        }
+    }
+
+    fn use_def(&mut self, tag: &Tag) -> Result<GeneratedValue, Box<dyn Error>> {
+        let (name, args) = match tag.args.split_first() {
+            Some((TagArg::Value(JsonValue::String(name)), args)) if tag.named_args.is_empty() => (name, args),
+            _ => return Err(Box::new(TagArgumentError::new(USE_TAG, "Expected a sub-template name"))),
+        };
+
+        if self.scopes.iter().any(|(n, _)| n == name) {
+            return Err(Box::new(RecursiveDefError::new(name)));
+        }
+
+        let def = self.defs.get(name).ok_or_else(|| UndefinedDefError::new(name))?.clone();
+        if def.params.len() != args.len() {
+            return Err(Box::new(TagArgumentError::new(USE_TAG,
+                &format!("{} expects {} arguments, got {}", name, def.params.len(), args.len()))));
+        }
+
+        // `evaluate_tag` binds every variable first, but a param is never left out if one is not.
+        let scope = def.params.into_iter()
+            .zip(args.iter())
+            .map(|(p, a)| match a {
+                TagArg::Value(v) => Ok((p, GeneratedValue::Value(v.clone()))),
+                TagArg::Variable(name) => Err(UndefinedVariableError::new(name)),
+            })
+            .collect::<Result<_, _>>()?;
+
+        self.scopes.push((name.clone(), scope));
+        let value = self.evaluate(&def.body);
+        self.scopes.pop();
+
+        value
+    }
+
+    fn variable(&self, name: &str) -> Result<&GeneratedValue, Box<dyn Error>> {
+        self.scopes.iter().rev()
+            .find_map(|(_, scope)| scope.get(name))
+            .or_else(|| self.vars.get(name))
+            .ok_or_else(|| Box::new(UndefinedVariableError::new(name)) as Box<dyn Error>)
+    }
+}
+
+fn parse_params(params: &str) -> Option<Vec<String>> {
+    params.split(|c: char| c == ',' || c.is_whitespace())
+        .map(|p| p.trim_start_matches('$'))
+        .filter(|p| !p.is_empty())
+        .map(|p| match p.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && p.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            true => Some(String::from(p)),
+            false => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::{DuplicateKeys, errors::ParseError, parse_lossless, parse_with_options};
+    use crate::test_dir::TestDir;
+
+    fn generate_str(template: &str, options: &GenerateOptions) -> Result<String, Box<dyn Error>> {
+        let mut expr = parse_with_options(template, &options.parse_options)?;
+        generate(&mut expr, options)
+    }
+
+    #[test]
+    fn generate_plain_template() {
+        let json = generate_str("{\"foo\": \"bar\", \"baz\": -123}", &GenerateOptions::default()).unwrap();
+
+        assert_eq!(json, "{\"foo\":\"bar\",\"baz\":-123}");
+    }
+
+    #[test]
+    fn generate_escapes_names() {
+        let json = generate_str(r#"{"first-name": 1, "a\"b\\c": 2, "\{{x}}": 3}"#, &GenerateOptions::default()).unwrap();
+
+        assert_eq!(json, r#"{"first-name":1,"a\"b\\c":2,"{{x}}":3}"#);
+    }
+
+    #[test]
+    fn generate_pick_from_word_list() {
+        let dir = TestDir::new("words", &[("colors.txt", "red\n")]);
+        let options = GenerateOptions { dictionary_dir: Some(dir.path().to_path_buf()), seed: Some(1), ..GenerateOptions::default() };
+
+        let json = generate_str("{\"color\": \"{{pick(\"colors\")}}\"}", &options).unwrap();
+
+        assert_eq!(json, "{\"color\":\"red\"}");
+    }
+
+    #[test]
+    fn generate_pick_from_weighted_csv() {
+        let dir = TestDir::new("csv", &[("skus.csv", "sku,weight\nA-1,0\n\"B \"\"2\"\"\",5\n")]);
+        let options = GenerateOptions { dictionary_dir: Some(dir.path().to_path_buf()), seed: Some(1), ..GenerateOptions::default() };
+
+        let json = generate_str("{\"sku\": \"sku {{ pick(\"skus\") }}\"}", &options).unwrap();
+
+        assert_eq!(json, "{\"sku\":\"sku B \\\"2\\\"\"}");
+    }
+
+    #[test]
+    fn generate_pick_missing_dictionary_is_err() {
+        let dir = TestDir::new("missing", &[]);
+        let options = GenerateOptions { dictionary_dir: Some(dir.path().to_path_buf()), seed: Some(1), ..GenerateOptions::default() };
+
+        assert!(generate_str("{\"a\": \"{{pick(\"nope\")}}\"}", &options).is_err());
+    }
+
+    #[test]
+    fn generate_file_returns_duplicate_key_warnings() {
+        let dir = TestDir::new("duplicates", &[
+            ("a.json", "{\"a\": 1, \"a\": 2, \"b\": \"{{include(\"b.json\")}}\"}"),
+            ("b.json", "{\"c\": 3, \"c\": 4}"),
+        ]);
+        let options = GenerateOptions {
+            parse_options: ParseOptions { duplicate_keys: DuplicateKeys::Warn, ..ParseOptions::default() },
+            ..GenerateOptions::default()
+        };
+
+        let (_, warnings) = generate_file_with_warnings(&dir.join("a.json"), &options).unwrap();
+        let names: Vec<_> = warnings.iter().map(|w| match &w.warning {
+            ParseError::DuplicateKey { name, .. } => (name.as_str(), w.path.as_ref().unwrap().file_name().unwrap().to_str().unwrap()),
+            e => panic!("Unexpected warning {}", e),
+        }).collect();
+
+        assert_eq!(names, vec![("a", "a.json"), ("c", "b.json")]);
+    }
+
+    #[test]
+    fn generate_returns_warnings_from_included_files() {
+        let dir = TestDir::new("included_duplicates", &[("b.json", "{\"c\": 3, \"c\": 4}")]);
+        let options = GenerateOptions {
+            parse_options: ParseOptions { duplicate_keys: DuplicateKeys::Warn, ..ParseOptions::default() },
+            base_dir: Some(dir.path().to_path_buf()),
+            ..GenerateOptions::default()
+        };
+        let mut expr = parse_with_options("{\"b\": \"{{include(\"b.json\")}}\"}", &options.parse_options).unwrap();
+
+        let (_, warnings) = generate_with_warnings(&mut expr, &options).unwrap();
+
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(&warnings[0].warning, ParseError::DuplicateKey { name, .. } if name == "c"));
+    }
+
+    #[test]
+    fn generate_from_lossless_tree_applies_duplicate_key_policy() {
+        let template = "{\"a\": 1, \"b\": 2, \"a\": 3}";
+
+        for (duplicate_keys, expected) in [(DuplicateKeys::FirstWins, "{\"a\":1,\"b\":2}"), (DuplicateKeys::LastWins, "{\"a\":3,\"b\":2}")] {
+            let options = GenerateOptions { parse_options: ParseOptions { duplicate_keys, ..ParseOptions::default() }, ..GenerateOptions::default() };
+            let mut document = parse_lossless(template, &options.parse_options).unwrap();
+
+            assert_eq!(generate(&mut document.value, &options).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn use_def_rejects_unbound_args() {
+        let mut generator = Generator::new(&GenerateOptions::default());
+        generator.defs.insert(String::from("point"), Def { params: vec![String::from("x")], body: Template::Value(JsonValue::Null) });
+        let tag = Tag {
+            name: String::from(USE_TAG),
+            args: vec![TagArg::Value(JsonValue::String(String::from("point"))), TagArg::Variable(String::from("x"))],
+            named_args: Vec::new(),
+        };
+
+        let e = generator.use_def(&tag).unwrap_err();
+
+        assert!(e.is::<UndefinedVariableError>());
+    }
+
+    #[test]
+    fn generate_shares_vars_across_document() {
+        let options = GenerateOptions { seed: Some(1), ..GenerateOptions::default() };
+        let template = "{\"$vars\": {\"tenant\": \"{{guid()}}\", \"key\": \"t {{$tenant}}\"}, \
+            \"tenant\": \"{{$tenant}}\", \"owner\": {\"tenant\": \"{{ $tenant }}\", \"key\": \"{{$key}}\"}}";
+
+        let json = generate_str(template, &options).unwrap();
+        let tenant = &json[11..47];
+
+        assert_eq!(json, format!("{{\"tenant\":\"{0}\",\"owner\":{{\"tenant\":\"{0}\",\"key\":\"t {0}\"}}}}", tenant));
+    }
+
+    #[test]
+    fn generate_keeps_variable_type() {
+        let template = "{\"$vars\": {\"n\": 5}, \"a\": \"{{$n}}\", \"b\": \"n {{$n}}\"}";
+
+        assert_eq!(generate_str(template, &GenerateOptions::default()).unwrap(), "{\"a\":5,\"b\":\"n 5\"}");
+    }
+
+    #[test]
+    fn generate_undefined_variable_is_err() {
+        let result = generate_str("{\"a\": \"{{$nope}}\"}", &GenerateOptions::default());
+
+        assert!(result.unwrap_err().is::<UndefinedVariableError>());
+    }
+
+    #[test]
+    fn generate_vars_must_be_object() {
+        let result = generate_str("{\"$vars\": 1}", &GenerateOptions::default());
+
+        assert!(result.unwrap_err().is::<InvalidSectionError>());
+    }
+
+    #[test]
+    fn generate_instantiates_defs_with_args() {
+        let template = "{\"$defs\": {\"money\": {\"$params\": \"min max\", \
+            \"amount\": \"{{rand($min, $max)}}\", \"currency\": \"USD\"}}, \
+            \"price\": \"{{use(\"money\", 10, 10)}}\", \"cost\": \"{{use(\"money\", 3, 3)}}\"}";
+
+        let json = generate_str(template, &GenerateOptions::default()).unwrap();
+
+        assert_eq!(json, "{\"price\":{\"amount\":10,\"currency\":\"USD\"},\"cost\":{\"amount\":3,\"currency\":\"USD\"}}");
+    }
+
+    #[test]
+    fn generate_defs_can_use_vars_and_other_defs() {
+        let template = "{\"$vars\": {\"n\": 7}, \"$defs\": {\"inner\": {\"$params\": \"x\", \"x\": \"{{$x}}\", \"n\": \"{{$n}}\"}, \
+            \"outer\": \"{{use(\"inner\", 1)}}\"}, \"a\": \"{{use(\"outer\")}}\"}";
+
+        let json = generate_str(template, &GenerateOptions::default()).unwrap();
+
+        assert_eq!(json, "{\"a\":{\"x\":1,\"n\":7}}");
+    }
+
+    macro_rules! generate_err_theory {
+        ($name:ident, $template:literal, $t:ty) => {
+            #[test]
+            fn $name() {
+                let result = generate_str($template, &GenerateOptions::default());
+
+                assert!(result.unwrap_err().is::<$t>());
+            }
+        }
+    }
+
+    generate_err_theory!(generate_undefined_def_is_err, "{\"a\": \"{{use(\"nope\")}}\"}", UndefinedDefError);
+    generate_err_theory!(generate_recursive_def_is_err,
+        "{\"$defs\": {\"a\": {\"b\": \"{{use(\"a\")}}\"}}, \"x\": \"{{use(\"a\")}}\"}", RecursiveDefError);
+    generate_err_theory!(generate_def_arity_is_checked,
+        "{\"$defs\": {\"a\": {\"$params\": \"x\", \"x\": \"{{$x}}\"}}, \"x\": \"{{use(\"a\")}}\"}", TagArgumentError);
+    generate_err_theory!(generate_def_params_are_scoped,
+        "{\"$defs\": {\"a\": {\"$params\": \"x\", \"x\": \"{{$x}}\"}}, \"b\": \"{{use(\"a\", 1)}}\", \"x\": \"{{$x}}\"}",
+        UndefinedVariableError);
+
+    #[test]
+    fn generate_keeps_numbers_exact_in_arbitrary_precision_mode() {
+        let options = GenerateOptions { parse_options: ParseOptions { arbitrary_precision: true, ..ParseOptions::default() }, ..GenerateOptions::default() };
+        let template = "{\"$vars\": {\"id\": 123456789012345678901234567890}, \"id\": \"{{$id}}\", \"key\": \"k{{$id}}\", \"n\": 1.50}";
+
+        assert_eq!(generate_str(template, &options).unwrap(),
+            "{\"id\":123456789012345678901234567890,\"key\":\"k123456789012345678901234567890\",\"n\":1.50}");
+    }
+
+    #[test]
+    fn generate_keeps_non_ascii_text() {
+        let options = GenerateOptions { seed: Some(1), locale: Some(String::from("de_DE")), ..GenerateOptions::default() };
+
+        let json = generate_str("{\"straße\": \"Grüße aus {{postalCode()}}\"}", &options).unwrap();
+
+        assert!(json.starts_with("{\"straße\":\"Grüße aus "));
+    }
+
+    #[test]
+    fn generate_nested_arrays_and_literals() {
+        let template = "{\"$vars\": {\"n\": 5}, \"a\": [\"{{$n}}\", [true, null], {\"b\": \"x{{$n}}\"}], \"c\": false}";
+
+        assert_eq!(generate_str(template, &GenerateOptions::default()).unwrap(),
+            "{\"a\":[5,[true,null],{\"b\":\"x5\"}],\"c\":false}");
+    }
+
+    #[test]
+    fn generate_lenient_template_outputs_strict_json() {
+        let options = GenerateOptions { parse_options: ParseOptions { lenient: true, ..ParseOptions::default() }, ..GenerateOptions::default() };
+        let template = "{\n  // the id\n  id: 0x10,\n  name: 'bob',\n  tags: ['a', 'b',],\n}";
+
+        assert_eq!(generate_str(template, &options).unwrap(), "{\"id\":16,\"name\":\"bob\",\"tags\":[\"a\",\"b\"]}");
+    }
+}
@@ -0,0 +1,315 @@
+use crate::ast::{
+    Expression,
+    JsonValue,
+    comma::CommaExpression,
+    comment::CommentExpression,
+    error::ErrorExpression,
+    json_array::JsonArrayExpression,
+    json_object::JsonObjectExpression,
+    name::NameExpression,
+    property_assignment::PropertyAssignmentExpression,
+    template::TemplateExpression,
+    value::ValueExpression,
+    visitor::ExpressionVisitor,
+    whitespace::WhitespaceExpression};
+use crate::parsing::{DuplicateKeys, ParseOptions, errors::ParseError, parse_reader_with_warnings};
+use crate::tags::{Segment, Tag, TagArg, errors::TagArgumentError};
+use super::errors::{IncludeCycleError, IncludeError};
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const INCLUDE_TAG: &str = "include";
+
+/// A parsed template with its tags resolved, ready to be generated from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Template {
+    Object(Vec<(String, Template)>),
+    Array(Vec<Template>),
+    Value(JsonValue),
+    /// A string that is exactly one tag, which may generate any type of value.
+    Tag(Tag),
+    /// A string that is exactly one `{{$variable}}`, which keeps the variable's type.
+    Variable(String),
+    /// A string mixing text and tags, which always generates a string.
+    Interpolated(Vec<Segment>),
+}
+
+impl Template {
+    fn from_segments(mut segments: Vec<Segment>) -> Template {
+        match segments.as_slice() {
+            [] => Template::Value(JsonValue::String(String::new())),
+            [Segment::Text(_)] | [Segment::Tag(_)] | [Segment::Variable(_)] => match segments.remove(0) {
+                Segment::Text(s) => Template::Value(JsonValue::String(s)),
+                Segment::Tag(t) => Template::Tag(t),
+                Segment::Variable(v) => Template::Variable(v),
+            },
+            _ => Template::Interpolated(segments),
+        }
+    }
+}
+
+/// A warning from parsing a template, such as a duplicate key, with the file it was found in.
+/// `path` is `None` for a template that was not read from a file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateWarning {
+    pub path: Option<PathBuf>,
+    pub warning: ParseError,
+}
+
+impl Display for TemplateWarning {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}. In {}", self.warning, path.display()),
+            None => write!(f, "{}", self.warning),
+        }
+    }
+}
+
+/// Parses the template in `path` and builds it, splicing in any files it includes. `includes` is
+/// the chain of files that included this one. Returns the warnings from this file and every file
+/// it includes.
+pub fn build_file(path: &Path, includes: &[PathBuf], options: &ParseOptions) -> Result<(Template, Vec<TemplateWarning>), Box<dyn Error>> {
+    let path = path.canonicalize().map_err(|e| IncludeError::new(path, Box::new(e)))?;
+
+    if includes.contains(&path) {
+        let mut chain = includes.to_vec();
+        chain.push(path);
+        return Err(Box::new(IncludeCycleError::new(chain)));
+    }
+
+    let in_file = |e: Box<dyn Error>| -> Box<dyn Error> {
+        if e.is::<IncludeError>() || e.is::<IncludeCycleError>() {
+            e
+        } else {
+            Box::new(IncludeError::new(&path, e))
+        }
+    };
+
+    let file = fs::File::open(&path).map_err(|e| in_file(Box::new(e)))?;
+    let (mut expr, warnings) = parse_reader_with_warnings(file, options).map_err(|e| in_file(Box::new(e)))?;
+
+    let mut builder = TemplateBuilder::new(path.parent().unwrap_or_else(|| Path::new("/")), options);
+    builder.includes = includes.to_vec();
+    builder.includes.push(path.clone());
+    builder.warnings = warnings.into_iter().map(|warning| TemplateWarning { path: Some(path.clone()), warning }).collect();
+
+    expr.accept(&mut builder);
+    builder.build().map_err(in_file)
+}
+
+fn is_include(segment: &Segment) -> bool {
+    match segment {
+        Segment::Tag(t) => t.name == INCLUDE_TAG,
+        _ => false,
+    }
+}
+
+/// Builds a `Template` from a parsed expression tree.
+pub struct TemplateBuilder {
+    frames: Vec<Template>,
+    names: Vec<String>,
+    result: Option<Template>,
+    error: Option<Box<dyn Error>>,
+    /// Directory that relative include paths are resolved against.
+    base_dir: PathBuf,
+    includes: Vec<PathBuf>,
+    parse_options: ParseOptions,
+    /// Warnings from the files that have been included so far.
+    warnings: Vec<TemplateWarning>,
+}
+
+impl TemplateBuilder {
+    pub fn new(base_dir: &Path, parse_options: &ParseOptions) -> TemplateBuilder {
+        TemplateBuilder {
+            frames: Vec::new(),
+            names: Vec::new(),
+            result: None,
+            error: None,
+            base_dir: base_dir.to_path_buf(),
+            includes: Vec::new(),
+            parse_options: parse_options.clone(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Returns the template along with the warnings from any files it included.
+    pub fn build(self) -> Result<(Template, Vec<TemplateWarning>), Box<dyn Error>> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        Ok((self.result.unwrap_or(Template::Object(Vec::new())), self.warnings))
+    }
+
+    /// Adds `t` to the open object or array. A member whose name is taken follows
+    /// `ParseOptions::duplicate_keys`, as a lossless tree keeps every member.
+    fn push(&mut self, t: Template) {
+        match self.frames.last_mut() {
+            Some(Template::Object(props)) => {
+                let name = self.names.pop().unwrap_or_default();
+                let policy = self.parse_options.duplicate_keys;
+                let taken = match policy {
+                    DuplicateKeys::FirstWins | DuplicateKeys::LastWins => props.iter().position(|(n, _)| *n == name),
+                    DuplicateKeys::Error | DuplicateKeys::Warn => None,
+                };
+
+                match taken {
+                    Some(i) if policy == DuplicateKeys::LastWins => props[i].1 = t,
+                    Some(_) => {},
+                    None => props.push((name, t)),
+                }
+            },
+            Some(Template::Array(items)) => items.push(t),
+            _ => self.result = Some(t),
+        }
+    }
+
+    fn include(&mut self, tag: &Tag) -> Result<Template, Box<dyn Error>> {
+        match tag.args.as_slice() {
+            [TagArg::Value(JsonValue::String(path))] if tag.named_args.is_empty() => {
+                let (template, warnings) = build_file(&self.base_dir.join(path), &self.includes, &self.parse_options)?;
+                self.warnings.extend(warnings);
+
+                Ok(template)
+            },
+            _ => Err(Box::new(TagArgumentError::new(INCLUDE_TAG, "Expected a file path"))),
+        }
+    }
+
+    fn finish_frame(&mut self) {
+        if let Some(t) = self.frames.pop() {
+            self.push(t);
+        }
+    }
+}
+
+impl ExpressionVisitor for TemplateBuilder {
+    fn visit_array(&mut self, expr: &mut JsonArrayExpression) {
+        self.frames.push(Template::Array(Vec::new()));
+
+        for e in expr.expressions.iter_mut() {
+            e.accept(self);
+        }
+
+        self.finish_frame();
+    }
+
+    fn visit_object(&mut self, expr: &mut JsonObjectExpression) {
+        self.frames.push(Template::Object(Vec::new()));
+
+        for e in expr.expressions.iter_mut() {
+            e.accept(self);
+        }
+
+        self.finish_frame();
+    }
+
+    fn visit_name(&mut self, expr: &mut NameExpression) {
+        self.names.push(expr.name.clone());
+    }
+
+    fn visit_property_assignment(&mut self, expr: &mut PropertyAssignmentExpression) {
+        expr.name.accept(self);
+        expr.value.accept(self);
+    }
+
+    fn visit_comma_expression(&mut self, _: &CommaExpression) {}
+
+    fn visit_value(&mut self, expr: &mut ValueExpression) {
+        self.push(Template::Value(expr.value.clone()));
+    }
+
+    fn visit_template(&mut self, expr: &mut TemplateExpression) {
+        let t = match Template::from_segments(expr.segments.clone()) {
+            Template::Tag(tag) if tag.name == INCLUDE_TAG => self.include(&tag),
+            Template::Interpolated(segments) if segments.iter().any(is_include) =>
+                Err(Box::new(TagArgumentError::new(INCLUDE_TAG, "An include must be the whole value")) as Box<dyn Error>),
+            t => Ok(t),
+        };
+
+        match t {
+            Ok(t) => self.push(t),
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+    }
+
+    fn visit_whitespace_expression(&mut self, _: &WhitespaceExpression) {}
+
+    fn visit_comment(&mut self, _: &CommentExpression) {}
+
+    fn visit_error(&mut self, expr: &ErrorExpression) {
+        self.error.get_or_insert(Box::new(expr.error.clone()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::errors::IncludeError;
+    use crate::test_dir::TestDir;
+
+    fn object(props: Vec<(&str, Template)>) -> Template {
+        Template::Object(props.into_iter().map(|(n, t)| (String::from(n), t)).collect())
+    }
+
+    fn string(s: &str) -> Template {
+        Template::Value(JsonValue::String(String::from(s)))
+    }
+
+    #[test]
+    fn build_file_splices_includes_relative_to_file() {
+        let dir = TestDir::new("relative", &[
+            ("user.json", "{\"name\": \"bob\", \"address\": \"{{include(\"parts/address.json\")}}\"}"),
+            ("parts/address.json", "{\"city\": \"{{include(\"city.json\")}}\"}"),
+            ("parts/city.json", "{\"name\": \"Paris\"}"),
+        ]);
+
+        let (template, _) = build_file(&dir.join("user.json"), &[], &ParseOptions::default()).unwrap();
+
+        assert_eq!(template, object(vec![
+            ("name", string("bob")),
+            ("address", object(vec![("city", object(vec![("name", string("Paris"))]))])),
+        ]));
+    }
+
+    #[test]
+    fn build_file_detects_cycles() {
+        let dir = TestDir::new("cycle", &[
+            ("a.json", "{\"b\": \"{{include(\"b.json\")}}\"}"),
+            ("b.json", "{\"a\": \"{{include(\"a.json\")}}\"}"),
+        ]);
+
+        let e = build_file(&dir.join("a.json"), &[], &ParseOptions::default()).unwrap_err();
+
+        assert!(e.is::<IncludeCycleError>());
+        assert!(e.to_string().contains("a.json -> "));
+    }
+
+    #[test]
+    fn build_file_reports_path_of_parse_error() {
+        let dir = TestDir::new("parse_err", &[
+            ("a.json", "{\"b\": \"{{include(\"b.json\")}}\"}"),
+            ("b.json", "{\n\"a\": ,}"),
+        ]);
+
+        let e = build_file(&dir.join("a.json"), &[], &ParseOptions::default()).unwrap_err();
+
+        assert!(e.is::<IncludeError>());
+        assert!(e.to_string().contains("b.json"));
+        assert!(e.to_string().contains("Line 2. Column 6"));
+    }
+
+    #[test]
+    fn build_file_rejects_interpolated_include() {
+        let dir = TestDir::new("interpolated", &[
+            ("a.json", "{\"b\": \"x {{include(\"b.json\")}}\"}"),
+            ("b.json", "{}"),
+        ]);
+
+        assert!(build_file(&dir.join("a.json"), &[], &ParseOptions::default()).is_err());
+    }
+}
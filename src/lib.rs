@@ -0,0 +1,8 @@
+pub mod ast;
+pub mod generator;
+pub mod parsing;
+pub mod tags;
+pub mod tokens;
+
+#[cfg(test)]
+mod test_dir;
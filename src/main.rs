@@ -1,26 +1,124 @@
-mod ast;
-mod parsing;
-mod tags;
-mod tokens;
-
-use ast::{
-    Expression,
-    visitor::{ExpressionVisitor, JsonExpressionVisitor}};
-use parsing::parse;
-use tokens::process_str;
-use std::collections::VecDeque;
+use better_json_generator::{
+    generator::{generate_file_with_warnings, generate_with_warnings, template::TemplateWarning, GenerateOptions},
+    parsing::{parse_many_with_warnings, parse_recovering, DuplicateKeys, ParseOptions}};
+use std::{env, fs, io::{self, Read}, path::{Path, PathBuf}, process};
+
+fn usage() -> ! {
+    eprintln!("Usage: better_json_generator [--dictionaries <dir>] [--locale <locale>] [--seed <n>] [--arbitrary-precision] [--lenient] [--duplicate-keys <error|warn|first|last>] [--lint] [template]");
+    process::exit(2);
+}
 
 fn main() {
-    let mut tokens = process_str("{\"foo\": \"bar\", \"baz\": -123, \"bing\": 5}");
-    let mut result = parse(tokens);
+    let mut options = GenerateOptions::default();
+    let mut template_path = None;
+    let mut lint = false;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" | "--dictionaries" => {
+                options.dictionary_dir = Some(PathBuf::from(args.next().unwrap_or_else(|| usage())));
+            },
+            "-l" | "--locale" => options.locale = Some(args.next().unwrap_or_else(|| usage())),
+            "--arbitrary-precision" => options.parse_options.arbitrary_precision = true,
+            "--lenient" => options.parse_options.lenient = true,
+            "--duplicate-keys" => {
+                options.parse_options.duplicate_keys = match args.next().as_deref() {
+                    Some("error") => DuplicateKeys::Error,
+                    Some("warn") => DuplicateKeys::Warn,
+                    Some("first") => DuplicateKeys::FirstWins,
+                    Some("last") => DuplicateKeys::LastWins,
+                    _ => usage(),
+                };
+            },
+            "--seed" => {
+                let seed = args.next().unwrap_or_else(|| usage());
+                options.seed = Some(seed.parse().unwrap_or_else(|_| usage()));
+            },
+            "--lint" => lint = true,
+            "-h" | "--help" => usage(),
+            _ => template_path = Some(arg),
+        }
+    }
+
+    if lint {
+        let template = match &template_path {
+            Some(path) => fs::read_to_string(path),
+            None => {
+                let mut template = String::new();
+                io::stdin().read_to_string(&mut template).map(|_| template)
+            },
+        };
+
+        match template {
+            Ok(template) => process::exit(lint_template(&template, &options.parse_options)),
+            Err(e) => {
+                eprintln!("Error reading template. {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    let result = match template_path {
+        Some(path) => generate_file_with_warnings(Path::new(&path), &options).map(|(json, warnings)| {
+            print_warnings(&warnings);
+            json
+        }),
+        None => {
+            let mut template = String::new();
+            if let Err(e) = io::stdin().read_to_string(&mut template) {
+                eprintln!("Error reading template. {}", e);
+                process::exit(1);
+            }
+
+            // Stdin may hold several templates, such as newline delimited JSON. Each generates one line.
+            let (documents, warnings) = parse_many_with_warnings(&template, &options.parse_options).unwrap_or_else(|e| {
+                eprint!("{}", e.render(&template));
+                process::exit(1);
+            });
+            for w in warnings.iter() {
+                eprintln!("{}", w.render_warning(&template));
+            }
+
+            documents.into_iter()
+                .map(|mut expr| generate_with_warnings(&mut expr, &options).map(|(json, warnings)| {
+                    print_warnings(&warnings);
+                    json
+                }))
+                .collect::<Result<Vec<_>, _>>()
+                .map(|lines| lines.join("\n"))
+        }
+    };
 
     match result {
-        Ok(mut expr) => {
-            let  v = &mut JsonExpressionVisitor::new();
-            expr.accept(v);
+        Ok(json) => println!("{}", json),
+        Err(e) => {
+            eprintln!("Error generating json. {}", e);
+            process::exit(1);
+        }
+    }
+}
 
-            println!("{}", v.get_json());
-        },
-        Err(e) => panic!("Error trying to parse tokens. {}", e)
+/// Prints warnings from the files a template was built from, rendered against the file they are in.
+fn print_warnings(warnings: &[TemplateWarning]) {
+    for w in warnings.iter() {
+        match w.path.as_deref().map(|path| (path, fs::read_to_string(path))) {
+            Some((path, Ok(source))) => eprint!("In {}\n{}", path.display(), w.warning.render_warning(&source)),
+            _ => eprintln!("warning: {}", w),
+        }
     }
 }
+
+/// Prints every syntax error and warning in the template, returning the exit code.
+fn lint_template(template: &str, options: &ParseOptions) -> i32 {
+    let (_, errors, warnings) = parse_recovering(template, options);
+
+    for e in errors.iter() {
+        eprintln!("{}", e.render(template));
+    }
+    for w in warnings.iter() {
+        eprintln!("{}", w.render_warning(template));
+    }
+
+    if errors.is_empty() { 0 } else { 1 }
+}
@@ -1,6 +1,61 @@
-use super::ParseContext;
-use crate::ast::{Expression, json_array::JsonArrayExpression};
+use super::{ParseContext, ParseResult};
+use super::object_expr::parse_value;
+use crate::ast::{comma::CommaExpression, json_array::JsonArrayExpression, node::Node};
+use crate::tokens::{Punct, TokenKind};
 
-pub fn parse_array_expr(ctx: &mut ParseContext) -> impl Expression {
-    JsonArrayExpression::new()
-}
\ No newline at end of file
+pub const ELEMENT: &[&str] = &["a value", "']'"];
+pub const AFTER_ELEMENT: &[&str] = &["','", "']'"];
+
+pub fn parse_array_expr(ctx: &mut ParseContext) -> ParseResult {
+    let start = ctx.start();
+    let mut ja = JsonArrayExpression::new();
+    // Set at the start and after each comma, when an element may come next.
+    let mut expect_element = true;
+    // Where the comma after the last element is in `ja.expressions`, and its span.
+    let mut comma = None;
+
+    loop {
+        for trivia in ctx.trivia() {
+            ja.add_expr(trivia);
+        }
+        let expected = if expect_element { ELEMENT } else { AFTER_ELEMENT };
+
+        if !ctx.advance() {
+            ctx.record(ctx.end_of_input(expected))?;
+            break;
+        }
+
+        match ctx.kind() {
+            Some(TokenKind::Punct(Punct::Comma)) if !expect_element => {
+                let span = ctx.span_from(ctx.start());
+                comma = Some((ja.expressions.len(), span));
+                ja.add_expr(CommaExpression { span, trailing: false });
+                expect_element = true;
+            },
+            Some(TokenKind::Punct(Punct::CloseSquareBrace)) => {
+                if let Some((index, span)) = comma.filter(|_| expect_element) {
+                    ctx.trailing_comma(&mut ja.expressions, index, span, &["a value"])?;
+                }
+                break;
+            },
+            // Closes an enclosing object, so when recovering this array ends here.
+            Some(TokenKind::Punct(Punct::CloseCurlyBrace)) => {
+                ctx.record(ctx.unexpected(expected))?;
+                ctx.rewind();
+                break;
+            },
+            _ => {
+                if !expect_element {
+                    ctx.record(ctx.unexpected(AFTER_ELEMENT))?;
+                }
+                ctx.rewind();
+                ja.add_expr(parse_value(ctx).or_else(|e| ctx.recover(e))?);
+                comma = None;
+                expect_element = false;
+            },
+        };
+    }
+
+    ja.span = ctx.span_from(start);
+    Ok(Node::Array(ja))
+}
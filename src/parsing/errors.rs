@@ -1,45 +1,160 @@
+use crate::ast::span::{Span, SpanShift};
 use std::error::Error;
 use std::fmt::{Display, Formatter, Result};
-use crate::tokens::Token;
-use std::borrow::Borrow;
-
-#[derive(Debug)]
-pub struct UnexpectedTokenError {
-    line: usize,
-    pos: usize,
-    token: Token
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParseError {
+    UnexpectedToken { found: String, span: Span, expected: Vec<&'static str> },
+    UnexpectedEndOfInput { span: Span, expected: Vec<&'static str> },
+    NumberOutOfRange { lexeme: String, span: Span },
+    /// Reading the input failed.
+    Io { message: String, span: Span },
+    InvalidUtf8 { span: Span },
+    /// Objects and arrays nest deeper than `ParseOptions::max_depth`.
+    TooDeep { max: usize, span: Span },
+    /// The input has more than `ParseOptions::max_tokens` tokens.
+    TooManyTokens { max: usize, span: Span },
+    /// A string is longer than `ParseOptions::max_string_length`.
+    StringTooLong { max: usize, span: Span },
+    /// An object has more than `ParseOptions::max_object_members` members.
+    TooManyMembers { max: usize, span: Span },
+    /// An object member has the same name as an earlier one, defined at `first`.
+    DuplicateKey { name: String, span: Span, first: Span },
 }
 
-impl UnexpectedTokenError {
-    pub fn new(line: usize, pos: usize, token: Token) -> impl Error {
-        UnexpectedTokenError {line, pos, token}
+impl ParseError {
+    /// A stable code identifying the kind of error, for tooling and documentation.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedToken { .. } => "E0001",
+            ParseError::UnexpectedEndOfInput { .. } => "E0002",
+            ParseError::NumberOutOfRange { .. } => "E0003",
+            ParseError::Io { .. } => "E0004",
+            ParseError::InvalidUtf8 { .. } => "E0005",
+            ParseError::TooDeep { .. } => "E0006",
+            ParseError::TooManyTokens { .. } => "E0007",
+            ParseError::StringTooLong { .. } => "E0008",
+            ParseError::TooManyMembers { .. } => "E0009",
+            ParseError::DuplicateKey { .. } => "E0010",
+        }
     }
-}
 
-impl Error for UnexpectedTokenError {}
+    /// Whether the error stopped the input being read, rather than being a mistake in it.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, ParseError::Io { .. } | ParseError::InvalidUtf8 { .. } | ParseError::TooManyTokens { .. }
+            | ParseError::StringTooLong { .. })
+    }
 
-impl Display for UnexpectedTokenError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "Unexpected token {}. Line {}. Position {}", char::from(&self.token), self.line, self.pos)
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEndOfInput { span, .. }
+            | ParseError::NumberOutOfRange { span, .. }
+            | ParseError::Io { span, .. }
+            | ParseError::InvalidUtf8 { span }
+            | ParseError::TooDeep { span, .. }
+            | ParseError::TooManyTokens { span, .. }
+            | ParseError::StringTooLong { span, .. }
+            | ParseError::TooManyMembers { span, .. }
+            | ParseError::DuplicateKey { span, .. } => *span,
+        }
     }
-}
 
-#[derive(Debug)]
-pub struct UnexpectedEndOfInputError {
-    line: usize,
-    pos: usize
+    /// Moves the spans to where their text is after an edit.
+    pub fn shift_spans(&mut self, shift: &SpanShift) {
+        match self {
+            ParseError::DuplicateKey { span, first, .. } => {
+                span.shift(shift);
+                first.shift(shift);
+            },
+            ParseError::UnexpectedToken { span, .. }
+            | ParseError::UnexpectedEndOfInput { span, .. }
+            | ParseError::NumberOutOfRange { span, .. }
+            | ParseError::Io { span, .. }
+            | ParseError::InvalidUtf8 { span }
+            | ParseError::TooDeep { span, .. }
+            | ParseError::TooManyTokens { span, .. }
+            | ParseError::StringTooLong { span, .. }
+            | ParseError::TooManyMembers { span, .. } => span.shift(shift),
+        }
+    }
+
+    /// What the parser would have accepted instead. Empty when anything else is wrong.
+    pub fn expected(&self) -> &[&'static str] {
+        match self {
+            ParseError::UnexpectedToken { expected, .. }
+            | ParseError::UnexpectedEndOfInput { expected, .. } => expected,
+            _ => &[],
+        }
+    }
+
+    /// Formats the error with the line of `source` it occurred on, underlining the span.
+    pub fn render(&self, source: &str) -> String {
+        self.render_as("error", source)
+    }
+
+    /// Formats the error like `render`, as a warning.
+    pub fn render_warning(&self, source: &str) -> String {
+        self.render_as("warning", source)
+    }
+
+    fn render_as(&self, severity: &str, source: &str) -> String {
+        let span = self.span();
+        let start = floor_char_boundary(source, span.start);
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+        let text = source[line_start..line_end].trim_end_matches('\r');
+
+        let end = floor_char_boundary(source, span.end.clamp(start, line_end));
+        let width = source[start..end].chars().count().max(1);
+
+        let gutter = " ".repeat(span.line.to_string().len());
+        format!("{}[{}]: {}\n{} --> line {}, column {}\n{} |\n{} | {}\n{} | {}{}\n",
+            severity, self.code(), self.message(),
+            gutter, span.line, span.column,
+            gutter,
+            span.line, text,
+            gutter, " ".repeat(span.column - 1), "^".repeat(width))
+    }
+
+    fn message(&self) -> String {
+        let message = match self {
+            ParseError::UnexpectedToken { found, .. } => format!("Unexpected token {}", found),
+            ParseError::UnexpectedEndOfInput { .. } => String::from("Unexpected end of input"),
+            ParseError::NumberOutOfRange { lexeme, .. } if lexeme.contains(['.', 'e', 'E']) && !lexeme.contains(['x', 'X']) =>
+                format!("Number {} does not fit in a 64 bit float", lexeme),
+            ParseError::NumberOutOfRange { lexeme, .. } => format!("Number {} does not fit in a 64 bit integer", lexeme),
+            ParseError::Io { message, .. } => format!("Error reading input. {}", message),
+            ParseError::InvalidUtf8 { .. } => String::from("Invalid UTF-8"),
+            ParseError::TooDeep { max, .. } => format!("Objects and arrays nest deeper than {} levels", max),
+            ParseError::TooManyTokens { max, .. } => format!("Input has more than {} tokens", max),
+            ParseError::StringTooLong { max, .. } => format!("String is longer than {} bytes", max),
+            ParseError::TooManyMembers { max, .. } => format!("Object has more than {} members", max),
+            ParseError::DuplicateKey { name, first, .. } =>
+                format!("Duplicate key \"{}\", first defined at line {}, column {}", name.escape_default(), first.line, first.column),
+        };
+
+        match self.expected() {
+            [] => message,
+            [e] => format!("{}. Expected {}", message, e),
+            [es @ .., last] => format!("{}. Expected {} or {}", message, es.join(", "), last),
+        }
+    }
 }
 
-impl UnexpectedEndOfInputError {
-    pub fn new(line: usize, pos: usize) -> impl Error {
-        UnexpectedEndOfInputError{line, pos}
+fn floor_char_boundary(s: &str, mut i: usize) -> usize {
+    i = i.min(s.len());
+    while !s.is_char_boundary(i) {
+        i -= 1;
     }
+    i
 }
 
-impl Error for UnexpectedEndOfInputError {}
+impl Error for ParseError {}
 
-impl Display for UnexpectedEndOfInputError {
+impl Display for ParseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> Result {
-        write!(f, "Unexpected end of input at line {}. pos {}", self.line, self.pos)
+        let span = self.span();
+        write!(f, "{}. Line {}. Column {}", self.message(), span.line, span.column)
     }
-}
\ No newline at end of file
+}
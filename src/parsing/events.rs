@@ -0,0 +1,414 @@
+use super::{ParseContext, ParseOptions, has_more, reader_lexer};
+use super::array_expr::{AFTER_ELEMENT, ELEMENT};
+use super::errors::ParseError;
+use super::object_expr::{AFTER_MEMBER, VALUE, expect_colon, member, parse_name, scalar_value, starts_name};
+use super::template_expr::parse_segments;
+use crate::ast::{JsonValue, span::Span};
+use crate::tags::Segment;
+use crate::tokens::{Punct, TokenKind};
+use std::io::Read;
+
+/// Something read from the input. An object's members are each a `Key` followed by the events of
+/// its value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonEvent {
+    StartObject,
+    EndObject,
+    StartArray,
+    EndArray,
+    Key(String),
+    Value(JsonValue),
+    /// A string with tags, as its text and tags.
+    Tag(Vec<Segment>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Container {
+    Object,
+    Array,
+}
+
+/// What the input may hold next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    Value,
+    Member { after_comma: bool },
+    AfterMember,
+    Element { after_comma: bool },
+    AfterElement,
+    /// The value has ended, so only whitespace may follow.
+    End,
+    Done,
+}
+
+/// Reads a single JSON value as events, without building a tree. Only the open objects and arrays
+/// are kept, so memory does not grow with the input.
+///
+/// The first error ends the events. Objects are not checked for duplicate keys, as that would
+/// mean keeping every name.
+pub struct JsonEvents<'a> {
+    ctx: ParseContext<'a>,
+    /// The open objects and arrays, with how many members or elements each has.
+    stack: Vec<(Container, usize)>,
+    state: State,
+    span: Span,
+}
+
+/// Reads `source` as events.
+pub fn parse_events<'a>(source: &'a str, options: &ParseOptions) -> JsonEvents<'a> {
+    JsonEvents::new(ParseContext::with_options(source, options))
+}
+
+/// Reads UTF-8 from `reader` as events, as they are needed.
+pub fn parse_events_reader<'a, R: Read + 'a>(reader: R, options: &ParseOptions) -> JsonEvents<'a> {
+    JsonEvents::new(ParseContext::from_source(reader_lexer(reader, options), options))
+}
+
+impl<'a> JsonEvents<'a> {
+    pub fn new(ctx: ParseContext<'a>) -> Self {
+        JsonEvents { ctx, stack: Vec::new(), state: State::Value, span: Span::default() }
+    }
+
+    /// Where the last event was read from. A name for `Key`, a brace for the others, except
+    /// values.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// How many objects and arrays are open.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    fn step(&mut self) -> Result<Option<JsonEvent>, ParseError> {
+        loop {
+            self.ctx.skip_trivia();
+
+            match self.state {
+                State::Done => return Ok(None),
+                State::End => {
+                    self.state = State::Done;
+                    if has_more(&mut self.ctx)? {
+                        self.ctx.advance();
+                        return Err(self.ctx.unexpected(&["end of input"]));
+                    }
+                    return Ok(None);
+                },
+                State::Value => return self.value().map(Some),
+                State::Member { after_comma } => {
+                    self.expect_token(member(&self.ctx.options))?;
+                    match self.ctx.kind() {
+                        Some(TokenKind::Punct(Punct::CloseCurlyBrace)) => {
+                            self.trailing_comma(after_comma, &["'\"'"])?;
+                            return Ok(Some(self.close(JsonEvent::EndObject)));
+                        },
+                        Some(kind) if starts_name(kind, &self.ctx.options) => return self.member().map(Some),
+                        _ => return Err(self.ctx.unexpected(member(&self.ctx.options))),
+                    }
+                },
+                State::Element { after_comma } => {
+                    self.expect_token(ELEMENT)?;
+                    match self.ctx.kind() {
+                        Some(TokenKind::Punct(Punct::CloseSquareBrace)) => {
+                            self.trailing_comma(after_comma, &["a value"])?;
+                            return Ok(Some(self.close(JsonEvent::EndArray)));
+                        },
+                        _ => {
+                            self.ctx.rewind();
+                            return self.value().map(Some);
+                        },
+                    }
+                },
+                State::AfterMember => {
+                    self.expect_token(AFTER_MEMBER)?;
+                    match self.ctx.kind() {
+                        Some(TokenKind::Punct(Punct::Comma)) => self.state = State::Member { after_comma: true },
+                        Some(TokenKind::Punct(Punct::CloseCurlyBrace)) => return Ok(Some(self.close(JsonEvent::EndObject))),
+                        _ => return Err(self.ctx.unexpected(AFTER_MEMBER)),
+                    }
+                },
+                State::AfterElement => {
+                    self.expect_token(AFTER_ELEMENT)?;
+                    match self.ctx.kind() {
+                        Some(TokenKind::Punct(Punct::Comma)) => self.state = State::Element { after_comma: true },
+                        Some(TokenKind::Punct(Punct::CloseSquareBrace)) => return Ok(Some(self.close(JsonEvent::EndArray))),
+                        _ => return Err(self.ctx.unexpected(AFTER_ELEMENT)),
+                    }
+                },
+            }
+        }
+    }
+
+    /// Moves to the next token, which must exist.
+    fn expect_token(&mut self, expected: &[&'static str]) -> Result<(), ParseError> {
+        if self.ctx.advance() {
+            Ok(())
+        } else {
+            Err(self.ctx.end_of_input(expected))
+        }
+    }
+
+    /// Trailing commas are only allowed in lenient mode.
+    fn trailing_comma(&self, after_comma: bool, expected: &[&'static str]) -> Result<(), ParseError> {
+        match after_comma && !self.ctx.options.lenient {
+            true => Err(self.ctx.unexpected(expected)),
+            false => Ok(()),
+        }
+    }
+
+    /// Reads a value, or the start of an object or array.
+    fn value(&mut self) -> Result<JsonEvent, ParseError> {
+        self.expect_token(VALUE)?;
+        let start = self.ctx.start();
+        self.span = self.ctx.span_from(start);
+
+        let event = match self.ctx.kind() {
+            Some(TokenKind::Punct(Punct::OpenCurlyBrace)) => return self.open(Container::Object),
+            Some(TokenKind::Punct(Punct::OpenSquareBrace)) => return self.open(Container::Array),
+            Some(TokenKind::TemplateHead(_)) => {
+                let segments = parse_segments(&mut self.ctx)?;
+                self.span = self.ctx.span_from(start);
+                JsonEvent::Tag(segments)
+            },
+            _ => JsonEvent::Value(scalar_value(&self.ctx).unwrap_or_else(|| Err(self.ctx.unexpected(VALUE)))?),
+        };
+
+        self.after_value();
+        Ok(event)
+    }
+
+    /// Reads a member's name and colon, the name being the current token.
+    fn member(&mut self) -> Result<JsonEvent, ParseError> {
+        let (_, members) = self.stack.last_mut().expect("an object should be open");
+        *members += 1;
+        let members = *members;
+
+        let name = parse_name(&mut self.ctx)?;
+        self.span = name.span;
+        if let Some(max) = self.ctx.options.max_object_members.filter(|&max| members > max) {
+            return Err(ParseError::TooManyMembers { max, span: name.span });
+        }
+
+        self.ctx.skip_trivia();
+        expect_colon(&mut self.ctx)?;
+        self.state = State::Value;
+
+        Ok(JsonEvent::Key(name.name))
+    }
+
+    fn open(&mut self, container: Container) -> Result<JsonEvent, ParseError> {
+        if let Some(max) = self.ctx.options.max_depth.filter(|&max| self.stack.len() >= max) {
+            return Err(ParseError::TooDeep { max, span: self.span });
+        }
+
+        self.stack.push((container, 0));
+        match container {
+            Container::Object => {
+                self.state = State::Member { after_comma: false };
+                Ok(JsonEvent::StartObject)
+            },
+            Container::Array => {
+                self.state = State::Element { after_comma: false };
+                Ok(JsonEvent::StartArray)
+            },
+        }
+    }
+
+    /// Ends the innermost object or array, whose closing brace is the current token.
+    fn close(&mut self, event: JsonEvent) -> JsonEvent {
+        self.span = self.ctx.span_from(self.ctx.start());
+        self.stack.pop();
+        self.after_value();
+
+        event
+    }
+
+    fn after_value(&mut self) {
+        self.state = match self.stack.last() {
+            Some((Container::Object, _)) => State::AfterMember,
+            Some((Container::Array, _)) => State::AfterElement,
+            None => State::End,
+        };
+    }
+}
+
+impl Iterator for JsonEvents<'_> {
+    type Item = Result<JsonEvent, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.step() {
+            Ok(event) => event.map(Ok),
+            Err(e) => {
+                self.state = State::Done;
+                Some(Err(e))
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::JsonEvent::*;
+    use crate::ast::visitor::{ExpressionVisitor, JsonExpressionVisitor, write_json_value};
+    use crate::parsing::parse;
+    use crate::tags::Tag as TemplateTag;
+
+    fn events(source: &str, options: &ParseOptions) -> Result<Vec<JsonEvent>, ParseError> {
+        parse_events(source, options).collect()
+    }
+
+    /// Writes events without whitespace, as `JsonExpressionVisitor` would write the tree.
+    fn write(events: &[JsonEvent]) -> String {
+        let mut json = String::new();
+        // Whether the next value in each open object or array follows another.
+        let mut follows = vec![false];
+        for event in events {
+            if matches!(event, StartObject | StartArray | Key(_) | Value(_) | Tag(_)) {
+                let last = follows.last_mut().unwrap();
+                if std::mem::replace(last, true) && !json.ends_with(": ") {
+                    json.push(',');
+                }
+            }
+            match event {
+                StartObject => json.push('{'),
+                StartArray => json.push('['),
+                EndObject => json.push('}'),
+                EndArray => json.push(']'),
+                Key(name) => {
+                    write_json_value(&JsonValue::String(name.clone()), &mut json);
+                    json.push_str(": ");
+                },
+                Value(value) => write_json_value(value, &mut json),
+                Tag(segments) => json.push_str(&format!("{:?}", segments)),
+            }
+            match event {
+                StartObject | StartArray => follows.push(false),
+                EndObject | EndArray => { follows.pop(); },
+                _ => {},
+            }
+        }
+
+        json
+    }
+
+    macro_rules! events_theory {
+        ($name:ident, $source:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!(events($source, &ParseOptions::default()), Ok($expected));
+            }
+        };
+    }
+
+    events_theory!(events_for_value, "42", vec![Value(JsonValue::Number(42))]);
+    events_theory!(events_for_empty_object, " { } ", vec![StartObject, EndObject]);
+    events_theory!(events_for_empty_array, "[]", vec![StartArray, EndArray]);
+    events_theory!(events_for_object, r#"{"a": 1, "b": [true, null]}"#, vec![
+        StartObject,
+        Key("a".to_string()), Value(JsonValue::Number(1)),
+        Key("b".to_string()), StartArray, Value(JsonValue::Boolean(true)), Value(JsonValue::Null), EndArray,
+        EndObject]);
+    events_theory!(events_for_nested_arrays, "[[], [[\"x\"]]]", vec![
+        StartArray, StartArray, EndArray, StartArray, StartArray, Value(JsonValue::String("x".to_string())), EndArray, EndArray,
+        EndArray]);
+    events_theory!(events_for_tag, r#"{"id": "id-{{guid}}"}"#, vec![
+        StartObject,
+        Key("id".to_string()),
+        Tag(vec![Segment::Text("id-".to_string()), Segment::Tag(TemplateTag { name: "guid".to_string(), ..TemplateTag::default() })]),
+        EndObject]);
+
+    #[test]
+    fn events_write_the_same_json_as_the_tree() {
+        let source = r#"{"a": [1, 2.5, -3e2], "b": {"c": "d\n", "e": [{}, [], null]}, "f": false}"#;
+
+        let mut visitor = JsonExpressionVisitor::new();
+        parse(source).unwrap().accept(&mut visitor);
+
+        let events = events(source, &ParseOptions::default()).unwrap();
+        assert_eq!(write(&events).replace(' ', ""), visitor.get_json().replace(' ', ""));
+    }
+
+    #[test]
+    fn events_allow_lenient_input() {
+        let options = ParseOptions { lenient: true, ..ParseOptions::default() };
+
+        assert_eq!(events("{a: 'b', /* c */ d: [0x10,], true: null,}", &options), Ok(vec![
+            StartObject,
+            Key("a".to_string()), Value(JsonValue::String("b".to_string())),
+            Key("d".to_string()), StartArray, Value(JsonValue::Number(16)), EndArray,
+            Key("true".to_string()), Value(JsonValue::Null),
+            EndObject]));
+    }
+
+    #[test]
+    fn events_end_at_the_first_error() {
+        let mut events = parse_events(r#"{"a": 1 "b": 2}"#, &ParseOptions::default());
+
+        assert_eq!(events.next(), Some(Ok(StartObject)));
+        assert_eq!(events.next(), Some(Ok(Key("a".to_string()))));
+        assert_eq!(events.next(), Some(Ok(Value(JsonValue::Number(1)))));
+        assert!(matches!(events.next(), Some(Err(ParseError::UnexpectedToken { .. }))));
+        assert_eq!(events.next(), None);
+    }
+
+    #[test]
+    fn events_reject_what_the_parser_rejects() {
+        for source in ["", "[1,]", "{\"a\": 1,}", "{\"a\" 1}", "[1 2]", "{1: 2}", "[1", "1 2", "}"] {
+            let parsed = parse(source).err();
+            assert!(parsed.is_some(), "{}", source);
+            assert_eq!(events(source, &ParseOptions::default()).err(), parsed, "{}", source);
+        }
+    }
+
+    #[test]
+    fn events_keep_duplicate_keys() {
+        let options = ParseOptions { duplicate_keys: crate::parsing::DuplicateKeys::Error, ..ParseOptions::default() };
+
+        assert_eq!(events(r#"{"a": 1, "a": 2}"#, &options).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn events_are_limited_in_depth() {
+        let options = ParseOptions { max_depth: Some(2), ..ParseOptions::default() };
+
+        assert!(events("[[1]]", &options).is_ok());
+        assert!(matches!(events("[[[1]]]", &options), Err(ParseError::TooDeep { max: 2, .. })));
+    }
+
+    #[test]
+    fn events_are_limited_in_members() {
+        let options = ParseOptions { max_object_members: Some(1), ..ParseOptions::default() };
+
+        assert!(events(r#"[{"a": 1}, {"b": 2}]"#, &options).is_ok());
+        assert!(matches!(events(r#"{"a": 1, "b": 2}"#, &options), Err(ParseError::TooManyMembers { max: 1, .. })));
+    }
+
+    #[test]
+    fn events_have_spans() {
+        let mut events = parse_events(r#"{"ab": "{{guid}}"}"#, &ParseOptions::default());
+        let mut spans = Vec::new();
+        while let Some(Ok(_)) = events.next() {
+            spans.push((events.span().start, events.span().end));
+        }
+
+        assert_eq!(spans, vec![(0, 1), (1, 5), (7, 17), (17, 18)]);
+    }
+
+    #[test]
+    fn events_are_read_from_a_reader() {
+        // Many elements, read in small pieces without the whole input being held.
+        let elements = 100_000;
+        let source = format!("[{}{{\"n\": 0}}]", "{\"n\": 0}, ".repeat(elements - 1));
+        let events = parse_events_reader(source.as_bytes(), &ParseOptions::default());
+
+        let mut objects = 0;
+        for event in events {
+            if event.unwrap() == StartObject {
+                objects += 1;
+            }
+        }
+
+        assert_eq!(objects, elements);
+    }
+}
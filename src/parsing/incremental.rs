@@ -0,0 +1,590 @@
+use super::{ParseContext, ParseOptions, has_more, parse_lossless_with_warnings};
+use super::errors::ParseError;
+use super::object_expr::parse_value;
+use crate::ast::{
+    comma::CommaExpression,
+    comment::CommentExpression,
+    document::Document,
+    error::ErrorExpression,
+    json_array::JsonArrayExpression,
+    json_object::JsonObjectExpression,
+    name::NameExpression,
+    node::Node,
+    property_assignment::PropertyAssignmentExpression,
+    span::{Position, Span, SpanShift},
+    template::TemplateExpression,
+    value::ValueExpression,
+    visitor::ExpressionVisitor,
+    whitespace::WhitespaceExpression};
+use crate::tokens::lexer::Lexer;
+use std::ops::Range;
+
+/// Replaces the bytes in `range` of a source with `text`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<usize>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<usize>, text: &str) -> TextEdit {
+        TextEdit { range, text: String::from(text) }
+    }
+}
+
+/// A source and its lossless tree, kept in step as the source is edited. An edit only reparses
+/// the smallest value, object or array around it; the rest of the tree is kept, with its spans
+/// moved. Warnings such as duplicate keys are kept in step the same way.
+pub struct IncrementalDocument {
+    source: String,
+    /// `None` after an edit left the source invalid.
+    document: Option<Document>,
+    warnings: Vec<ParseError>,
+    options: ParseOptions,
+}
+
+impl IncrementalDocument {
+    pub fn new(source: &str, options: &ParseOptions) -> Result<IncrementalDocument, ParseError> {
+        let (document, warnings) = parse_lossless_with_warnings(source, options)?;
+
+        Ok(IncrementalDocument {
+            source: String::from(source),
+            document: Some(document),
+            warnings,
+            options: options.clone(),
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The tree, unless the last edit left the source invalid. Changing the tree does not
+    /// change the source.
+    pub fn document(&mut self) -> Option<&mut Document> {
+        self.document.as_mut()
+    }
+
+    /// The warnings a full parse of the source would give, in order. Empty while the source is
+    /// invalid.
+    pub fn warnings(&self) -> &[ParseError] {
+        &self.warnings
+    }
+
+    /// Applies `edit` to the source and updates the tree, returning the span of what was
+    /// reparsed. When the source is then invalid the error is returned and there is no tree
+    /// until an edit makes it valid again. Limits in `ParseOptions` apply to what is reparsed.
+    ///
+    /// Panics if the range is out of bounds or not on character boundaries.
+    pub fn apply(&mut self, edit: &TextEdit) -> Result<Span, ParseError> {
+        let start = position_of(&self.source, self.document.as_ref(), edit.range.start);
+        let old_end = advance(start, &self.source[edit.range.clone()]);
+        let new_end = advance(start, &edit.text);
+        self.source.replace_range(edit.range.clone(), &edit.text);
+
+        let shift = SpanShift::new(old_end, new_end);
+        let reparsed = match self.document.as_mut() {
+            Some(document) => reparse(document, &self.source, edit, shift, &self.options),
+            None => None,
+        };
+
+        if let Some((span, warnings)) = reparsed {
+            // The reparsed text's warnings replace those it had before the edit.
+            let old_end = span.end.wrapping_add_signed(-shift.offset);
+            self.warnings.retain(|w| w.span().start < span.start || w.span().end > old_end);
+            self.warnings.iter_mut().for_each(|w| w.shift_spans(&shift));
+            self.warnings.extend(warnings);
+            self.warnings.sort_by_key(|w| w.span().start);
+
+            return Ok(span);
+        }
+
+        match parse_lossless_with_warnings(&self.source, &self.options) {
+            Ok((document, warnings)) => {
+                self.document = Some(document);
+                self.warnings = warnings;
+                Ok(Span { start: 0, end: self.source.len(), line: 1, column: 1 })
+            },
+            Err(e) => {
+                self.document = None;
+                self.warnings.clear();
+                Err(e)
+            },
+        }
+    }
+}
+
+/// Reparses the smallest part of `document` around the edit, returning its span and warnings, or
+/// `None` when the whole source needs parsing.
+fn reparse(document: &mut Document, source: &str, edit: &TextEdit, shift: SpanShift, options: &ParseOptions) -> Option<(Span, Vec<ParseError>)> {
+    let mut reparser = Reparser {
+        source,
+        edit: edit.range.clone(),
+        shift,
+        options,
+        depth: 0,
+        replacement: None,
+        handled: None,
+        warnings: Vec::new(),
+    };
+
+    let value = document.value.span();
+    if value.start > edit.range.start || edit.range.end > value.end {
+        return None;
+    }
+
+    document.value.accept(&mut reparser);
+    if let Some(replacement) = reparser.replacement.take() {
+        reparser.handled = Some(replacement.span());
+        document.value = replacement;
+    }
+
+    let handled = reparser.handled?;
+    for e in document.trailing.iter_mut() {
+        e.shift_spans(&reparser.shift);
+    }
+
+    Some((handled, reparser.warnings))
+}
+
+/// Where `offset` is in `source`, counted from the start of the deepest node of `document` that
+/// starts at or before it, so that an edit does not scan the whole source.
+fn position_of(source: &str, document: Option<&Document>, offset: usize) -> Position {
+    let anchor = document.map_or_else(Position::default, |document| anchor(&document.value, offset));
+    advance(anchor, &source[anchor.offset..offset])
+}
+
+/// The start of the deepest node in `node` that starts at or before `offset`.
+fn anchor(mut node: &Node, offset: usize) -> Position {
+    let mut anchor = Position::default();
+
+    loop {
+        let span = node.span();
+        if span.start > offset {
+            return anchor;
+        }
+        anchor = span.start_position();
+
+        let children = match node {
+            Node::Object(e) => &e.expressions,
+            Node::Array(e) => &e.expressions,
+            Node::Property(e) if e.value.span().start <= offset => {
+                node = &e.value;
+                continue;
+            },
+            _ => return anchor,
+        };
+
+        match children.partition_point(|child| child.span().start <= offset) {
+            0 => return anchor,
+            i => node = &children[i - 1],
+        }
+    }
+}
+
+/// The position after `text`, which starts at `start`.
+fn advance(start: Position, text: &str) -> Position {
+    text.chars().fold(start, Position::after)
+}
+
+/// Finds the smallest value, object or array that the edit is strictly inside of and reparses
+/// it. Containers around it keep their other children, whose spans are moved.
+struct Reparser<'a> {
+    source: &'a str,
+    /// The edited range of the old source.
+    edit: Range<usize>,
+    shift: SpanShift,
+    options: &'a ParseOptions,
+    /// How many objects and arrays are open.
+    depth: usize,
+    /// What the visited expression is to be replaced with.
+    replacement: Option<Node>,
+    /// The span of what was reparsed, once something has been.
+    handled: Option<Span>,
+    /// Warnings from parsing the replacement.
+    warnings: Vec<ParseError>,
+}
+
+impl Reparser<'_> {
+    /// Whether the edit is inside `span` without touching its first or last character.
+    fn strictly_inside(&self, span: Span) -> bool {
+        span.start < self.edit.start && self.edit.end < span.end
+    }
+
+    fn container(&mut self, expressions: &mut [Node], span: &mut Span) {
+        if !self.strictly_inside(*span) {
+            return;
+        }
+
+        self.depth += 1;
+        for i in 0..expressions.len() {
+            let child = expressions[i].span();
+            if child.start > self.edit.start || self.edit.end > child.end {
+                continue;
+            }
+
+            expressions[i].accept(self);
+            if let Some(replacement) = self.replacement.take() {
+                self.handled = Some(replacement.span());
+                expressions[i] = replacement;
+            }
+
+            if self.handled.is_some() {
+                for e in expressions[i + 1..].iter_mut() {
+                    e.shift_spans(&self.shift);
+                }
+                span.shift(&self.shift);
+                self.depth -= 1;
+                return;
+            }
+        }
+        self.depth -= 1;
+
+        self.replacement = self.parse(*span);
+    }
+
+    /// Parses the text that was at `span` before the edit as a single value.
+    fn parse(&mut self, span: Span) -> Option<Node> {
+        let end = span.end.wrapping_add_signed(self.shift.offset);
+        let lexer = Lexer::starting_at(&self.source[..end], self.options.lenient, span.start_position())
+            .with_max_string_length(self.options.max_string_length);
+        let mut ctx = ParseContext::from_source(lexer, self.options);
+        ctx.lossless = Some(self.source);
+        ctx.depth = self.depth;
+
+        let value = parse_value(&mut ctx).ok()?;
+        match has_more(&mut ctx) {
+            Ok(false) if value.span().start == span.start && value.span().end == end => {
+                self.warnings = ctx.warnings;
+                Some(value)
+            },
+            _ => None,
+        }
+    }
+
+    fn leaf(&mut self, span: Span) {
+        if self.strictly_inside(span) {
+            self.replacement = self.parse(span);
+        }
+    }
+}
+
+impl ExpressionVisitor for Reparser<'_> {
+    fn visit_array(&mut self, expr: &mut JsonArrayExpression) {
+        self.container(&mut expr.expressions, &mut expr.span);
+    }
+
+    fn visit_object(&mut self, expr: &mut JsonObjectExpression) {
+        self.container(&mut expr.expressions, &mut expr.span);
+    }
+
+    fn visit_name(&mut self, _: &mut NameExpression) {}
+
+    /// Only an edit inside the value is handled here. The object reparses any other.
+    fn visit_property_assignment(&mut self, expr: &mut PropertyAssignmentExpression) {
+        let value = expr.value.span();
+        if value.start > self.edit.start || self.edit.end > value.end {
+            return;
+        }
+
+        expr.value.accept(self);
+        if let Some(replacement) = self.replacement.take() {
+            self.handled = Some(replacement.span());
+            *expr.value = replacement;
+        }
+    }
+
+    fn visit_comma_expression(&mut self, _: &CommaExpression) {}
+
+    fn visit_value(&mut self, expr: &mut ValueExpression) {
+        self.leaf(expr.span);
+    }
+
+    fn visit_template(&mut self, expr: &mut TemplateExpression) {
+        self.leaf(expr.span);
+    }
+
+    fn visit_whitespace_expression(&mut self, _: &WhitespaceExpression) {}
+
+    fn visit_comment(&mut self, _: &CommentExpression) {}
+
+    fn visit_error(&mut self, _: &ErrorExpression) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Expression;
+    use crate::ast::visitor::SourceExpressionVisitor;
+    use crate::parsing::{DuplicateKeys, parse_lossless};
+
+    /// Records every node with its span, in order.
+    #[derive(Default)]
+    struct SpanCollector {
+        spans: Vec<(&'static str, Span)>,
+    }
+
+    impl ExpressionVisitor for SpanCollector {
+        fn visit_array(&mut self, expr: &mut JsonArrayExpression) {
+            self.spans.push(("array", expr.span));
+            expr.expressions.iter_mut().for_each(|e| e.accept(self));
+        }
+
+        fn visit_object(&mut self, expr: &mut JsonObjectExpression) {
+            self.spans.push(("object", expr.span));
+            expr.expressions.iter_mut().for_each(|e| e.accept(self));
+        }
+
+        fn visit_name(&mut self, expr: &mut NameExpression) {
+            self.spans.push(("name", expr.span));
+        }
+
+        fn visit_property_assignment(&mut self, expr: &mut PropertyAssignmentExpression) {
+            self.spans.push(("property", expr.span()));
+            expr.name.accept(self);
+            expr.before_colon.iter_mut().for_each(|e| e.accept(self));
+            expr.after_colon.iter_mut().for_each(|e| e.accept(self));
+            expr.value.accept(self);
+        }
+
+        fn visit_comma_expression(&mut self, expr: &CommaExpression) {
+            self.spans.push(("comma", expr.span));
+        }
+
+        fn visit_value(&mut self, expr: &mut ValueExpression) {
+            self.spans.push(("value", expr.span));
+        }
+
+        fn visit_template(&mut self, expr: &mut TemplateExpression) {
+            self.spans.push(("template", expr.span));
+        }
+
+        fn visit_whitespace_expression(&mut self, expr: &WhitespaceExpression) {
+            self.spans.push(("whitespace", expr.span));
+        }
+
+        fn visit_comment(&mut self, expr: &CommentExpression) {
+            self.spans.push(("comment", expr.span));
+        }
+
+        fn visit_error(&mut self, _: &ErrorExpression) {}
+    }
+
+    fn spans(document: &mut Document) -> Vec<(&'static str, Span)> {
+        let mut v = SpanCollector::default();
+        document.accept(&mut v);
+        v.spans
+    }
+
+    /// Applies `edit` and checks the tree and warnings match a full parse of the new source,
+    /// returning what was reparsed.
+    fn apply(doc: &mut IncrementalDocument, edit: TextEdit) -> Result<Span, ParseError> {
+        let reparsed = doc.apply(&edit)?;
+        let (mut expected, warnings) = parse_lossless_with_warnings(doc.source(), &doc.options).unwrap();
+        assert_eq!(doc.warnings(), warnings.as_slice());
+
+        let source = String::from(doc.source());
+        let document = doc.document().unwrap();
+
+        let mut v = SourceExpressionVisitor::new();
+        document.accept(&mut v);
+        assert_eq!(v.source, source);
+        assert_eq!(spans(document), spans(&mut expected));
+
+        Ok(reparsed)
+    }
+
+    const SOURCE: &str = "{\n  \"a\": [1, 2, {\"b\": \"x-{{guid}}\"}],\n  \"c\": {\"d\": true}\n}\n";
+
+    fn doc(source: &str) -> IncrementalDocument {
+        IncrementalDocument::new(source, &ParseOptions { lenient: true, ..ParseOptions::default() }).unwrap()
+    }
+
+    #[test]
+    fn edit_inside_a_value_reparses_only_the_value() {
+        let mut doc = doc("[12, 3]");
+        let reparsed = apply(&mut doc, TextEdit::new(2..2, "4567")).unwrap();
+
+        assert_eq!(doc.source(), "[145672, 3]");
+        assert_eq!(reparsed, Span { start: 1, end: 7, line: 1, column: 2 });
+    }
+
+    #[test]
+    fn edit_inside_a_template_reparses_only_the_template() {
+        let mut doc = doc(SOURCE);
+        let start = SOURCE.find("guid").unwrap();
+        let reparsed = apply(&mut doc, TextEdit::new(start..start + 4, "pick(\"colors\")")).unwrap();
+
+        assert_eq!(&doc.source()[reparsed.start..reparsed.end], "\"x-{{pick(\"colors\")}}\"");
+    }
+
+    #[test]
+    fn edit_between_members_reparses_the_object() {
+        let mut doc = doc(SOURCE);
+        let start = SOURCE.find("true}").unwrap() + 4;
+        let reparsed = apply(&mut doc, TextEdit::new(start..start, ", \"e\": [\n]")).unwrap();
+
+        assert_eq!(&doc.source()[reparsed.start..reparsed.end], "{\"d\": true, \"e\": [\n]}");
+    }
+
+    #[test]
+    fn edits_across_lines_move_later_spans() {
+        let mut doc = doc(SOURCE);
+        apply(&mut doc, TextEdit::new(1..1, "\n\n  ")).unwrap();
+
+        let one = doc.source().find('1').unwrap();
+        apply(&mut doc, TextEdit::new(one..one + 1, "100")).unwrap();
+        apply(&mut doc, TextEdit::new(one..one + 3, "0,\n 1")).unwrap();
+
+        let t = doc.source().find("true").unwrap();
+        let reparsed = apply(&mut doc, TextEdit::new(t + 1..t + 3, "ru")).unwrap();
+        assert_eq!((reparsed.line, reparsed.column), (6, 14));
+    }
+
+    #[test]
+    fn edits_to_names_and_braces_reparse_more() {
+        let mut doc = doc(SOURCE);
+        let name = SOURCE.find("\"b\"").unwrap();
+        let reparsed = apply(&mut doc, TextEdit::new(name + 1..name + 2, "bee")).unwrap();
+        assert_eq!(&doc.source()[reparsed.start..reparsed.end], "{\"bee\": \"x-{{guid}}\"}");
+
+        let reparsed = apply(&mut doc, TextEdit::new(0..1, "{ ")).unwrap();
+        assert_eq!(reparsed, Span { start: 0, end: doc.source().len(), line: 1, column: 1 });
+    }
+
+    #[test]
+    fn edits_that_change_tokens_around_them_reparse_more() {
+        let mut doc = doc("[\"ab\", 12]");
+        let reparsed = apply(&mut doc, TextEdit::new(2..2, "\", \"")).unwrap();
+        assert_eq!(reparsed, Span { start: 0, end: 14, line: 1, column: 1 });
+
+        let reparsed = apply(&mut doc, TextEdit::new(12..12, ", 3")).unwrap();
+        assert_eq!(doc.source(), "[\"\", \"ab\", 1, 32]");
+        assert_eq!(reparsed.start, 0);
+    }
+
+    #[test]
+    fn comments_can_end_past_the_edit() {
+        let mut doc = doc("{\"a\": [1, 2], \"b\": 3 /* x */}");
+        assert!(doc.apply(&TextEdit::new(9..10, "/*")).is_err());
+        assert!(doc.document().is_none());
+    }
+
+    #[test]
+    fn invalid_edits_are_reported_until_fixed() {
+        let mut doc = doc(SOURCE);
+        let start = SOURCE.find("true").unwrap();
+
+        assert!(doc.apply(&TextEdit::new(start..start + 4, "tru")).is_err());
+        assert!(doc.document().is_none());
+        assert!(apply(&mut doc, TextEdit::new(start + 3..start + 3, "e")).is_ok());
+        assert_eq!(doc.source(), SOURCE);
+    }
+
+    #[test]
+    fn reparsing_keeps_depth_limits() {
+        let options = ParseOptions { max_depth: Some(2), ..ParseOptions::default() };
+        let mut doc = IncrementalDocument::new("[[1], 2]", &options).unwrap();
+
+        let e = doc.apply(&TextEdit::new(2..3, "[1]")).unwrap_err();
+        assert_eq!(e.code(), "E0006");
+    }
+
+    /// Makes random edits to `source`, checking each against a full parse.
+    fn random_edits(source: &str) {
+        const SNIPPETS: &[&str] = &["", "1", "-2.5", " ", "\n", ",", "\"", "\"k\": ", "[", "]", "{", "}", "{{n}}", "/* c */", "null"];
+        let mut doc = doc(source);
+        let mut seed: u64 = 7;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            (seed >> 33) as usize % n
+        };
+
+        for _ in 0..1_000 {
+            let len = doc.source().len();
+            let start = next(len + 1);
+            let end = (start + next(4)).min(len);
+            let edit = TextEdit::new(start..end, SNIPPETS[next(SNIPPETS.len())]);
+
+            let expected = {
+                let mut source = String::from(doc.source());
+                source.replace_range(start..end, &edit.text);
+                parse_lossless(&source, &doc.options).is_ok()
+            };
+            let undo = TextEdit::new(start..start + edit.text.len(), &doc.source()[start..end]);
+            assert_eq!(apply(&mut doc, edit).is_ok(), expected);
+
+            // Undoes an invalid edit so that the source stays mostly valid.
+            if !expected {
+                apply(&mut doc, undo).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn random_edits_match_full_parses() {
+        random_edits(SOURCE);
+    }
+
+    #[test]
+    fn random_edits_keep_duplicate_key_warnings() {
+        random_edits("{\"k\": 1, \"a\": [1, {\"k\": 2, \"k\": \"{{n}}\"}], \"k\": {\"k\": 3}, \"a\": null}\n");
+    }
+
+    #[test]
+    fn warnings_follow_edits() {
+        let mut doc = doc("{\"a\": 1, \"a\": 2, \"b\": {\"c\": [3], \"c\": 4}}");
+        assert_eq!(doc.warnings().len(), 2);
+
+        // Moves the warnings after the edit without reparsing them.
+        apply(&mut doc, TextEdit::new(6..7, "\n100")).unwrap();
+        assert_eq!(doc.warnings()[1].span().line, 2);
+
+        let c = doc.source().rfind("\"c\"").unwrap();
+        apply(&mut doc, TextEdit::new(c + 1..c + 2, "d")).unwrap();
+        assert_eq!(doc.warnings().len(), 1);
+
+        let b = doc.source().find("\"b\"").unwrap();
+        apply(&mut doc, TextEdit::new(b + 1..b + 2, "a")).unwrap();
+        assert!(matches!(&doc.warnings()[1], ParseError::DuplicateKey { name, .. } if name == "a"));
+
+        assert!(doc.apply(&TextEdit::new(0..1, "")).is_err());
+        assert!(doc.warnings().is_empty());
+    }
+
+    #[test]
+    fn edits_keep_duplicate_keys_whatever_the_policy() {
+        for duplicate_keys in [DuplicateKeys::FirstWins, DuplicateKeys::LastWins] {
+            let mut doc = IncrementalDocument::new("{\"a\": 1}", &ParseOptions { duplicate_keys, ..ParseOptions::default() }).unwrap();
+            apply(&mut doc, TextEdit::new(7..7, ", \"a\": 5")).unwrap();
+
+            assert_eq!(doc.source(), "{\"a\": 1, \"a\": 5}");
+            assert_eq!(doc.warnings().len(), 1);
+        }
+    }
+
+    #[test]
+    fn edits_to_large_templates_reparse_only_what_changed() {
+        let mut source = String::from("[\n");
+        for i in 0..2_000 {
+            source.push_str(&format!("  {{\"id\": {}, \"name\": \"user {{{{guid}}}}\", \"tags\": [1, 2.5, true, null]}},\n", i));
+        }
+        source.push_str("  {\"last\": 0}\n]\n");
+        let mut doc = doc(&source);
+
+        for at in [source.find("2.5").unwrap(), source.len() / 2, source.rfind('0').unwrap()] {
+            let at = doc.source()[at..].find(|c: char| c.is_ascii_digit()).unwrap() + at;
+            let reparsed = apply(&mut doc, TextEdit::new(at..at + 1, "7")).unwrap();
+
+            assert!(reparsed.end - reparsed.start < 100, "reparsed {:?}", reparsed);
+        }
+    }
+
+    #[test]
+    fn top_level_scalars_can_be_edited() {
+        let mut doc = doc(" \"abc\" // c\n");
+        let reparsed = apply(&mut doc, TextEdit::new(2..3, "\\u00e9")).unwrap();
+
+        assert_eq!(reparsed, Span { start: 1, end: 11, line: 1, column: 2 });
+    }
+}
@@ -1,52 +1,194 @@
-mod errors;
+pub mod errors;
+pub mod events;
+pub mod incremental;
 mod array_expr;
 mod object_expr;
+mod template_expr;
 
-use crate::ast::{Expression, *};
-use crate::tokens::Token;
-use std::iter::{Iterator, FromIterator};
-use std::slice::IterMut;
+use crate::ast::{
+    node::Node,
+    comma::CommaExpression,
+    comment::CommentExpression,
+    document::Document,
+    error::ErrorExpression,
+    span::{Position, Span},
+    whitespace::WhitespaceExpression};
+use crate::tags::Segment;
+use crate::tokens::{Punct, Token, TokenKind, lexer::Lexer};
 use std::vec::Vec;
-use crate::ast::json_object::JsonObjectExpression;
-use crate::ast::property_assignment::PropertyAssignmentExpression;
-use crate::ast::whitespace::WhitespaceExpression;
-use crate::ast::comma::CommaExpression;
-use std::error::Error;
 use std::collections::VecDeque;
-use crate::ast::json_array::JsonArrayExpression;
-use array_expr::parse_array_expr;
-use object_expr::parse_object_expr;
+use std::io::{BufReader, Read};
+use errors::ParseError;
+use object_expr::parse_value;
 
-pub type ParseResult = Result<Box<dyn Expression>, Box<dyn Error>>;
+pub type ParseResult = Result<Node, ParseError>;
 
-pub struct ParseContext {
-    pos: usize,
-    line: usize,
-    tokens: VecDeque<Token>,
-    current: Option<Token>,
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    /// Keep every number as its validated text, as `JsonValue::Decimal`, so numbers of any size
+    /// or precision survive a round trip.
+    pub arbitrary_precision: bool,
+    /// Accept JSON5 style templates, with `//` and `/* */` comments, trailing commas, unquoted
+    /// and single quoted names, single quoted strings and hex numbers. The AST is still strict
+    /// JSON.
+    pub lenient: bool,
+    /// How deeply objects and arrays may nest. Limited by default, so that deeply nested input
+    /// cannot overflow the stack.
+    pub max_depth: Option<usize>,
+    /// How many tokens, including whitespace and comments, the input may have.
+    pub max_tokens: Option<usize>,
+    /// How many bytes a string, name or the text between tags may have once decoded.
+    pub max_string_length: Option<usize>,
+    /// How many members an object may have.
+    pub max_object_members: Option<usize>,
+    /// What to do with an object member whose name an earlier member already has.
+    pub duplicate_keys: DuplicateKeys,
 }
 
-impl ParseContext {
-    pub fn new(tokens: Vec<Token>) -> ParseContext {
+/// What to do with an object member whose name an earlier member already has. Except with
+/// `Error`, each duplicate is reported as a `ParseError::DuplicateKey` warning. `parse_lossless`
+/// keeps every member whatever the policy, and generating from its tree applies the policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Fail the parse.
+    Error,
+    /// Keep every member.
+    #[default]
+    Warn,
+    /// Keep the first member and leave out the duplicates.
+    FirstWins,
+    /// Keep the first member's place but the last member's value.
+    LastWins,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            arbitrary_precision: false,
+            lenient: false,
+            max_depth: Some(DEFAULT_MAX_DEPTH),
+            max_tokens: None,
+            max_string_length: None,
+            max_object_members: None,
+            duplicate_keys: DuplicateKeys::default(),
+        }
+    }
+}
+
+pub const DEFAULT_MAX_DEPTH: usize = 128;
+
+pub struct ParseContext<'a> {
+    source: Box<dyn Iterator<Item = Token<'a>> + 'a>,
+    /// Tokens read from `source` but not consumed yet.
+    tokens: VecDeque<Token<'a>>,
+    /// The error that ended `source`, reported wherever the parser needed more input.
+    source_error: Option<ParseError>,
+    /// Where `source` ended, once it has.
+    eof: Option<Span>,
+    current: Option<Token<'a>>,
+    /// Where the input consumed so far ends.
+    end: usize,
+    /// How many tokens have been read from `source`.
+    token_count: usize,
+    /// How many objects and arrays are open.
+    depth: usize,
+    options: ParseOptions,
+    /// Errors recorded while recovering. `None` when the first error ends the parse.
+    errors: Option<Vec<ParseError>>,
+    warnings: Vec<ParseError>,
+    /// The source, when the tree keeps every byte of it.
+    lossless: Option<&'a str>,
+}
+
+impl<'a> ParseContext<'a> {
+    pub fn new(source: &'a str) -> ParseContext<'a> {
+        ParseContext::with_options(source, &ParseOptions::default())
+    }
+
+    pub fn with_options(source: &'a str, options: &ParseOptions) -> ParseContext<'a> {
+        ParseContext::from_source(Lexer::new(source, options.lenient).with_max_string_length(options.max_string_length), options)
+    }
+
+    /// Parses tokens as they are produced, such as by a `Lexer` over a reader. The lexer should
+    /// be given `options.max_string_length`.
+    pub fn from_source(source: impl Iterator<Item = Token<'a>> + 'a, options: &ParseOptions) -> ParseContext<'a> {
         ParseContext {
-            pos: 0,
-            line: 0,
-            tokens: VecDeque::from(tokens),
+            source: Box::new(source),
+            tokens: VecDeque::new(),
+            source_error: None,
+            eof: None,
             current: None,
+            end: 0,
+            token_count: 0,
+            depth: 0,
+            options: options.clone(),
+            errors: None,
+            warnings: Vec::new(),
+            lossless: None,
         }
     }
-    pub fn advance(&mut self) -> bool {
-        if let Some(t) = self.tokens.pop_front() {
-            match t {
-                Token::Whitespace('\n') => {
-                    self.pos = 0;
-                    self.line = self.line + 1;
-                }
-                _ => {
-                    self.pos = self.pos + 1;
+
+    /// Reads a token from the source if none are waiting, returning whether there is one.
+    fn fill(&mut self) -> bool {
+        if self.tokens.is_empty() && self.eof.is_none() && self.source_error.is_none() {
+            match self.source.next().map(|t| self.check_limits(t)) {
+                Some(Token { kind: TokenKind::Eof, span }) => self.eof = Some(span),
+                Some(Token { kind: TokenKind::Error(e), .. }) if e.is_fatal() => self.source_error = Some(e),
+                Some(t) => self.tokens.push_back(t),
+                None => self.eof = Some(Span { start: self.end, end: self.end, ..Span::default() }),
+            }
+        }
+
+        !self.tokens.is_empty()
+    }
+
+    /// Replaces a token that breaks `max_tokens` with its error. The lexer enforces
+    /// `max_string_length` itself, so that it stops reading a string that is too long.
+    fn check_limits(&mut self, token: Token<'a>) -> Token<'a> {
+        let span = token.span;
+
+        if !matches!(token.kind, TokenKind::Eof) {
+            self.token_count += 1;
+            if let Some(max) = self.options.max_tokens.filter(|&max| self.token_count > max) {
+                return Token { kind: TokenKind::Error(ParseError::TooManyTokens { max, span }), span };
+            }
+        }
+
+        token
+    }
+
+    /// Parses an object or array, whose opening brace is the current token, one level deeper.
+    /// When recovering, a value nested too deeply is skipped whole.
+    pub fn nested(&mut self, parse: fn(&mut ParseContext<'a>) -> ParseResult) -> ParseResult {
+        if let Some(max) = self.options.max_depth.filter(|&max| self.depth >= max) {
+            let span = self.current.as_ref().expect("there should be a current token").span;
+            let e = ParseError::TooDeep { max, span };
+            self.record(e.clone())?;
+
+            let mut open = 1;
+            while open > 0 && self.advance() {
+                match self.kind() {
+                    Some(TokenKind::Punct(Punct::OpenCurlyBrace)) | Some(TokenKind::Punct(Punct::OpenSquareBrace)) => open += 1,
+                    Some(TokenKind::Punct(Punct::CloseCurlyBrace)) | Some(TokenKind::Punct(Punct::CloseSquareBrace)) => open -= 1,
+                    _ => {},
                 }
             }
 
+            return Ok(Node::Error(ErrorExpression::new(e)));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+
+        result
+    }
+
+    pub fn advance(&mut self) -> bool {
+        self.fill();
+
+        if let Some(t) = self.tokens.pop_front() {
+            self.end = t.span.end;
             self.current = Some(t);
             true
         } else {
@@ -54,9 +196,19 @@ impl ParseContext {
         }
     }
 
+    pub fn peek(&mut self) -> Option<&Token<'a>> {
+        self.fill();
+        self.tokens.front()
+    }
+
+    /// The kind of the current token.
+    pub fn kind(&self) -> Option<&TokenKind<'a>> {
+        self.current.as_ref().map(|t| &t.kind)
+    }
+
     pub fn rewind(&mut self) -> bool {
-        if let Some(t) = self.current {
-            self.current = None;
+        if let Some(t) = self.current.take() {
+            self.end = t.span.start;
             self.tokens.push_front(t);
 
             true
@@ -64,248 +216,1122 @@ impl ParseContext {
             false
         }
     }
-}
 
-pub fn parse(tokens: Vec<Token>) -> ParseResult {
-    let context = &mut ParseContext::new(tokens);
+    /// An error for the current token. A malformed token is its own error.
+    pub fn unexpected(&self, expected: &[&'static str]) -> ParseError {
+        let token = self.current.as_ref().expect("there should be a current token");
 
-    if context.advance() {
-        return match context.current.as_ref().unwrap() {
-            Token::OpenCurlyBrace => {
-                Ok(parse_object_expr(context)?)
-            },
-            Token::OpenSquareBrace => {
-                Ok(Box::new(parse_array_expr(context)))
-            },
-            t => {
-                Err(Box::new(errors::UnexpectedTokenError::new(context.line, context.pos, *t)))
+        match &token.kind {
+            TokenKind::Error(e) => e.clone(),
+            kind => ParseError::UnexpectedToken { found: kind.to_string(), span: token.span, expected: expected.to_vec() },
+        }
+    }
+
+    /// An error for running out of input, or for whatever stopped the input being read.
+    pub fn end_of_input(&self, expected: &[&'static str]) -> ParseError {
+        if let Some(e) = &self.source_error {
+            return e.clone();
+        }
+
+        let span = self.eof.expect("the input should have ended");
+        ParseError::UnexpectedEndOfInput { span, expected: expected.to_vec() }
+    }
+
+    /// Skips whitespace and comments. The lexer only produces comments in lenient mode.
+    pub fn skip_trivia(&mut self) {
+        while let Some(TokenKind::Whitespace(_)) | Some(TokenKind::Comment(_)) = self.peek().map(|t| &t.kind) {
+            self.advance();
+        }
+    }
+
+    /// Skips whitespace and comments, returning them as nodes when parsing losslessly.
+    pub fn trivia(&mut self) -> Vec<Node> {
+        let mut trivia = Vec::new();
+
+        while let Some(TokenKind::Whitespace(_)) | Some(TokenKind::Comment(_)) = self.peek().map(|t| &t.kind) {
+            self.advance();
+            if self.lossless.is_none() {
+                continue;
             }
-        };
-    } else {
-        Err(Box::new(errors::UnexpectedEndOfInputError::new(context.line, context.pos)))
+
+            let span = self.current.as_ref().expect("there should be a current token").span;
+            let text = self.raw(span);
+            if let Some(TokenKind::Whitespace(_)) = self.kind() {
+                let mut whitespace = WhitespaceExpression::with_text(&text);
+                whitespace.span = span;
+                trivia.push(Node::Whitespace(whitespace));
+            } else {
+                trivia.push(Node::Comment(CommentExpression { text, span }));
+            }
+        }
+
+        trivia
+    }
+
+    /// The source text of `span` when parsing losslessly. Otherwise empty.
+    pub fn raw(&self, span: Span) -> String {
+        self.lossless.map_or_else(String::new, |source| String::from(&source[span.start..span.end]))
+    }
+
+    /// Handles a comma at `index` in `expressions` that the current token, a closing brace,
+    /// follows. Trailing commas are only allowed in lenient mode, and are left out unless parsing
+    /// losslessly.
+    pub fn trailing_comma(&mut self, expressions: &mut Vec<Node>, index: usize, span: Span,
+                          expected: &[&'static str]) -> Result<(), ParseError> {
+        if !self.options.lenient {
+            return self.record(self.unexpected(expected));
+        }
+
+        if self.lossless.is_some() {
+            expressions[index] = Node::Comma(CommaExpression { span, trailing: true });
+        } else {
+            expressions.truncate(index);
+        }
+
+        Ok(())
+    }
+
+    /// Where the current token starts.
+    pub fn start(&self) -> Position {
+        self.current.as_ref().expect("there should be a current token").span.start_position()
+    }
+
+    /// The span from `start` to the end of the current token.
+    pub fn span_from(&self, start: Position) -> Span {
+        Span::new(start, self.end)
+    }
+
+    /// Records `e` when recovering, otherwise returns it.
+    pub fn record(&mut self, e: ParseError) -> Result<(), ParseError> {
+        match self.errors.as_mut() {
+            // Running out of input ends every open object and array, but is only one error.
+            Some(errors) => match (errors.last(), &e) {
+                (Some(ParseError::UnexpectedEndOfInput { .. }), ParseError::UnexpectedEndOfInput { .. }) => Ok(()),
+                (Some(last), e) if last == e => Ok(()),
+                _ => {
+                    errors.push(e);
+                    Ok(())
+                },
+            },
+            None => Err(e),
+        }
+    }
+
+    /// Records a problem that does not stop the parse.
+    pub fn warn(&mut self, e: ParseError) {
+        self.warnings.push(e);
+    }
+
+    /// When recovering, records `e` and skips to the next `,`, `}` or `]`, returning an error node
+    /// in place of whatever failed to parse. Otherwise returns `e`.
+    pub fn recover(&mut self, e: ParseError) -> ParseResult {
+        self.record(e.clone())?;
+
+        // The token that failed may itself be where to resume.
+        if self.kind().is_some_and(is_sync) {
+            self.rewind();
+        }
+
+        while self.peek().is_some_and(|t| !is_sync(&t.kind)) {
+            self.advance();
+        }
+
+        Ok(Node::Error(ErrorExpression::new(e)))
+    }
+}
+
+/// Whether recovery resumes at the token.
+fn is_sync(kind: &TokenKind) -> bool {
+    matches!(kind, TokenKind::Punct(Punct::Comma) | TokenKind::Punct(Punct::CloseCurlyBrace) | TokenKind::Punct(Punct::CloseSquareBrace))
+}
+
+pub fn parse(source: &str) -> ParseResult {
+    parse_with_options(source, &ParseOptions::default())
+}
+
+/// Parses a single JSON value of any type. Only whitespace may follow it.
+pub fn parse_with_options(source: &str, options: &ParseOptions) -> ParseResult {
+    parse_document(&mut ParseContext::with_options(source, options))
+}
+
+/// Parses a single JSON value keeping every byte of `source`, including whitespace, comments and
+/// how names and values are written, so that `SourceExpressionVisitor` writes it back unchanged.
+pub fn parse_lossless(source: &str, options: &ParseOptions) -> Result<Document, ParseError> {
+    parse_lossless_with_warnings(source, options).map(|(document, _)| document)
+}
+
+/// Parses a `Document` like `parse_lossless`, also returning warnings such as duplicate keys.
+pub fn parse_lossless_with_warnings(source: &str, options: &ParseOptions) -> Result<(Document, Vec<ParseError>), ParseError> {
+    let context = &mut ParseContext::with_options(source, options);
+    context.lossless = Some(source);
+
+    let leading = context.trivia();
+    let value = parse_value(context)?;
+    let trailing = context.trivia();
+
+    if has_more(context)? {
+        context.advance();
+        return Err(context.unexpected(&["end of input"]));
     }
+
+    Ok((Document { leading, value, trailing }, std::mem::take(&mut context.warnings)))
+}
+
+/// Parses a single JSON value, also returning warnings such as duplicate keys.
+pub fn parse_with_warnings(source: &str, options: &ParseOptions) -> Result<(Node, Vec<ParseError>), ParseError> {
+    let context = &mut ParseContext::with_options(source, options);
+    let expr = parse_document(context)?;
+
+    Ok((expr, std::mem::take(&mut context.warnings)))
+}
+
+/// Parses a single JSON value from UTF-8 read from `reader` as it is needed.
+pub fn parse_reader<R: Read>(reader: R, options: &ParseOptions) -> ParseResult {
+    parse_reader_with_warnings(reader, options).map(|(expr, _)| expr)
+}
+
+/// Parses a single JSON value from UTF-8 read from `reader`, also returning warnings such as
+/// duplicate keys.
+pub fn parse_reader_with_warnings<R: Read>(reader: R, options: &ParseOptions) -> Result<(Node, Vec<ParseError>), ParseError> {
+    let context = &mut ParseContext::from_source(reader_lexer(reader, options), options);
+    let expr = parse_document(context)?;
+
+    Ok((expr, std::mem::take(&mut context.warnings)))
+}
+
+/// A lexer over UTF-8 read from `reader`, limited as `options` say.
+fn reader_lexer<'a, R: Read + 'a>(reader: R, options: &ParseOptions) -> Lexer<'a> {
+    Lexer::from_reader(BufReader::new(reader), options.lenient).with_max_string_length(options.max_string_length)
 }
 
-// pub fn parse(tokens: &mut Vec<Token>) -> impl Expression {
-//     let iter= &mut tokens.iter_mut();
-//     if iter.len() < 1 {
-//         panic!("No tokens to parse");
-//     }
-//
-//     let first_token = iter.nth(0).expect("Should be at least 1");
-//
-//     let exp = match first_token {
-//         Token::OpenCurlyBrace => parse_object_expression(iter),
-//         t => panic!(format!("Unexpected token at position 0: {:?}. Expected {{ or [", t))
-//     };
-//
-//     exp
-// }
-//
-// fn parse_object_expression(iter: &mut IterMut<Token>) -> impl Expression {
-//     let mut obj_expr = JsonObjectExpression::new();
-//
-//     loop {
-//         if let Some(t) = iter.next() {
-//             match t {
-//                 Token::Whitespace(c) => {
-//                     let ws: Box<dyn Expression> = Box::new(WhitespaceExpression::new(*c));
-//                     obj_expr.add_expr(ws);
-//                 },
-//                  Token::Quote => {
-//                      let q: Box<dyn Expression> = Box::new(parse_property_expression(iter));
-//                      obj_expr.add_expr(q);
-//                  },
-//                 Token::Comma => {
-//                     let comm: Box<dyn Expression> = Box::new(CommaExpression::new());
-//                     obj_expr.add_expr( comm);
-//                 },
-//                 Token::CloseCurlyBrace => break,
-//                 Token::Unknown(c) => panic!(format!("Unexpected token at position : {:?}", c)),
-//                 _ => {
-//                     println!("{:?}", obj_expr);
-//                     unimplemented!()
-//                 }
-//             }
-//         } else {
-//             break;
-//         }
-//     }
-//
-//     obj_expr
-// }
-//
-// fn parse_property_expression(iter: &mut IterMut<Token>) -> impl Expression {
-//     let mut name = String::new();
-//
-//     loop {
-//         if let Some(t) = iter.next() {
-//             match t {
-//                 Token::Character(c) => {
-//                     name = format!("{}{}", name, c);
-//                 },
-//                 Token::Quote => {
-//                     let n = iter.next();
-//                     if let Some(token) = n {
-//                         match token {
-//                             Token::Colon => break,
-//                             u => panic!("Unexpected token {:?}", u)
-//                         }
-//                     } else {
-//                         panic!("Unexpected end of input");
-//                     }
-//                 },
-//                 u => panic!("Unexpected token {:?} at pos . Expected [A-Z|a-z]", u)
-//             }
-//         }
-//     }
-//
-//     loop {
-//         if let Some(t) = iter.next() {
-//             match t {
-//                 Token::Whitespace(_) => {
-//                     continue
-//                 },
-//                 Token::Quote => {
-//                     let mut s = String::new();
-//                     while let Some(token) = peekable.next() {
-//                         match token {
-//                             Token::Character(c) => {
-//                                 s = format!("{}{}", s, c);
-//                             },
-//                             Token::Quote => {
-//                                 return PropertyAssignmentExpression::new(name, JsonValue::String(s.clone()))
-//                             },
-//                             c => panic!("Unexpected token {:?} at pos .", c)
-//                         }
-//                     }
-//                 },
-//                 Token::Digit(d) => {
-//                     let digits = &mut vec![d];
-//
-//                     while let Some(token) = iter.next() {
-//                         match token {
-//                             Token::Digit(i) => digits.push(i),
-//                             Token::Comma | Token::CloseCurlyBrace => {
-//                                 let base: i64 = 10;
-//                                 let k = digits.len() - 1;
-//                                 let mut result:i64 = 0;
-//                                 for (i, j) in digits.iter().enumerate() {
-//                                     let p: i64 = (k - i) as i64;
-//                                     let val: i64 = ((**j as i64) * base.pow(p as u32)) as i64;
-//                                     result = result + val;
-//                                 }
-//                                 return PropertyAssignmentExpression::new(name, JsonValue::Number(result));
-//                             },
-//                             u => panic!("Unexpected token {:?} at pos . Expected digit ',' or '}}'", u)
-//                         }
-//                     }
-//                     panic!("Unexpected end of input");
-//                 },
-//                 Token::Character(c) => {
-//                     let mut chars = vec![c];
-//
-//                     while let Some(token) = iter.next() {
-//                         match token {
-//                             Token::Character(b) => chars.push(*b),
-//                             Token::Comma | Token::CloseCurlyBrace => {
-//                                 let result= String::from_iter(chars.iter());
-//
-//                                 let result = match result.as_str() {
-//                                     "true" => true,
-//                                     "false" => false,
-//                                     _ => panic!("Unexpected value {:?}. Expected 'true' or 'false'")
-//                                 };
-//
-//                                 return PropertyAssignmentExpression::new(name, JsonValue::Boolean(result));
-//                             },
-//                             u => panic!("unexpected token {:?} at pos . Expected character or ',' or '}}'", u)
-//                         }
-//                     }
-//                     panic!("Unexpected end of input");
-//                 },
-//                 other => unimplemented!("{:?}", other)
-//             }
-//         }
-//     }
-// }
-//
-//
+fn parse_document(context: &mut ParseContext) -> ParseResult {
+    let expr = parse_value(context)?;
+
+    if has_more(context)? {
+        context.advance();
+        return Err(context.unexpected(&["end of input"]));
+    }
+
+    Ok(expr)
+}
+
+/// Parses a single JSON value, recovering from syntax errors so that every error is reported.
+/// Whatever failed to parse is replaced by an `ErrorExpression` in the returned tree. Returns the
+/// tree, the errors and the warnings.
+pub fn parse_recovering(source: &str, options: &ParseOptions) -> (Node, Vec<ParseError>, Vec<ParseError>) {
+    let context = &mut ParseContext::with_options(source, options);
+    context.errors = Some(Vec::new());
+
+    let expr = parse_value(context).or_else(|e| context.recover(e)).expect("errors should be recorded");
+
+    let more = has_more(context).or_else(|e| context.record(e).map(|_| false));
+    if more.expect("errors should be recorded") {
+        context.advance();
+        let e = context.unexpected(&["end of input"]);
+        context.record(e).expect("errors should be recorded");
+    }
+
+    (expr, context.errors.take().unwrap_or_default(), std::mem::take(&mut context.warnings))
+}
+
+/// Parses a single string, such as `"id-{{guid()}}"`, into its text and tags.
+pub fn parse_segments(source: &str) -> Result<Vec<Segment>, ParseError> {
+    let context = &mut ParseContext::new(source);
+    context.skip_trivia();
+
+    if !context.advance() {
+        return Err(context.end_of_input(&["a string"]));
+    }
+
+    let segments = match context.kind() {
+        Some(TokenKind::String(s)) if s.is_empty() => Vec::new(),
+        Some(TokenKind::String(s)) => vec![Segment::Text(s.to_string())],
+        Some(TokenKind::TemplateHead(_)) => template_expr::parse_segments(context)?,
+        _ => return Err(context.unexpected(&["a string"])),
+    };
+
+    if has_more(context)? {
+        context.advance();
+        return Err(context.unexpected(&["end of input"]));
+    }
+
+    Ok(segments)
+}
+
+pub fn parse_many(source: &str) -> Result<Vec<Node>, ParseError> {
+    parse_many_with_options(source, &ParseOptions::default())
+}
+
+/// Parses a sequence of JSON values separated by whitespace, such as newline delimited JSON.
+pub fn parse_many_with_options(source: &str, options: &ParseOptions) -> Result<Vec<Node>, ParseError> {
+    parse_many_with_warnings(source, options).map(|(documents, _)| documents)
+}
+
+/// Parses a sequence of JSON values, also returning warnings such as duplicate keys.
+pub fn parse_many_with_warnings(source: &str, options: &ParseOptions) -> Result<(Vec<Node>, Vec<ParseError>), ParseError> {
+    let context = &mut ParseContext::with_options(source, options);
+    let documents = parse_documents(context)?;
+
+    Ok((documents, std::mem::take(&mut context.warnings)))
+}
+
+/// Parses a sequence of JSON values from UTF-8 read from `reader` as it is needed.
+pub fn parse_many_reader<R: Read>(reader: R, options: &ParseOptions) -> Result<Vec<Node>, ParseError> {
+    parse_documents(&mut ParseContext::from_source(reader_lexer(reader, options), options))
+}
+
+fn parse_documents(context: &mut ParseContext) -> Result<Vec<Node>, ParseError> {
+    let mut documents = Vec::new();
+
+    while has_more(context)? {
+        documents.push(parse_value(context)?);
+    }
+
+    Ok(documents)
+}
+
+/// Skips whitespace and comments, returning whether any tokens are left.
+fn has_more(ctx: &mut ParseContext) -> Result<bool, ParseError> {
+    ctx.skip_trivia();
+
+    match (ctx.peek().is_some(), &ctx.source_error) {
+        (false, Some(e)) => Err(e.clone()),
+        (more, _) => Ok(more),
+    }
+}
 
 #[cfg(test)]
 mod parsecontext_tests {
     use super::*;
+    use crate::tokens::Literal;
 
     #[test]
-    fn parsecontext_does_not_advance_empty_tokens() {
-        let mut pc = ParseContext::new(Vec::new());
+    fn parsecontext_does_not_advance_empty_input() {
+        let mut pc = ParseContext::new("");
 
         let result = pc.advance();
 
         assert!(!result);
         assert_eq!(pc.current, None)
     }
+
+    #[test]
+    fn parsecontext_tracks_lines_columns_and_offsets() {
+        let mut pc = ParseContext::new("[\"é\",\n1]");
+
+        pc.advance();
+        pc.advance();
+        assert_eq!(pc.start(), Position { offset: 1, line: 1, column: 2 });
+        pc.advance();
+        pc.advance();
+        pc.advance();
+        assert_eq!(pc.start(), Position { offset: 7, line: 2, column: 1 });
+    }
+
+    #[test]
+    fn parsecontext_rewind_restores_position() {
+        let mut pc = ParseContext::new("null\ntrue");
+
+        pc.advance();
+        pc.advance();
+        pc.rewind();
+        assert_eq!(pc.span_from(Position::default()).end, 4);
+        pc.advance();
+
+        assert_eq!(pc.kind(), Some(&TokenKind::Whitespace("\n".into())));
+        assert_eq!(pc.start(), Position { offset: 4, line: 1, column: 5 });
+        pc.advance();
+        assert_eq!(pc.kind(), Some(&TokenKind::Literal(Literal::True)));
+        assert_eq!(pc.start(), Position { offset: 5, line: 2, column: 1 });
+    }
 }
 
 #[cfg(test)]
 mod parse_tests {
-    use crate::tokens::Token;
     use super::*;
-    use crate::parsing::errors::{UnexpectedEndOfInputError, UnexpectedTokenError};
-    use std::borrow::Borrow;
-    use std::any::TypeId;
+    use crate::ast::{Expression, JsonValue};
+    use crate::ast::visitor::{ExpressionVisitor, JsonExpressionVisitor, SourceExpressionVisitor, write_json_string};
+    use crate::ast::{
+        comma::CommaExpression,
+        comment::CommentExpression,
+        json_array::JsonArrayExpression,
+        json_object::JsonObjectExpression,
+        name::NameExpression,
+        property_assignment::PropertyAssignmentExpression,
+        template::TemplateExpression,
+        value::ValueExpression,
+        whitespace::WhitespaceExpression};
 
     macro_rules! assert_err {
-        ($e:ident, $t:ty) => {{
-            println!("{}", $e.is::<$t>());
-            assert!($e.is::<$t>());
+        ($e:ident, $t:ident) => {{
+            assert!(matches!($e, ParseError::$t { .. }), "{:?}", $e);
         }}
     }
 
-    // macro_rules! assert_ok {
-    //     ($e:ident)
-    // }
-
     macro_rules! err_test {
-        ($name:ident, $v:expr, $t:ty) => {
+        ($name:ident, $v:expr, $t:ident) => {
             #[test]
             fn $name() {
-                let tokens = $v;
-
-                match parse(tokens) {
+                match parse($v) {
                     Err(e) => assert_err!(e, $t),
-                    _ => assert!(false)
+                    _ => panic!("expected an error")
                 }
             }
         }
     }
 
-    err_test!(parse_returns_err_empty_tokens, Vec::new(), UnexpectedEndOfInputError);
-    err_test!(parse_returns_err_if_start_close_curly_brace, vec![Token::CloseCurlyBrace], UnexpectedTokenError);
-    err_test!(parse_returns_err_if_start_close_square_brace, vec![Token::CloseSquareBrace], UnexpectedTokenError);
-    err_test!(parse_returns_err_if_start_open_parens, vec![Token::OpenParenthesis], UnexpectedTokenError);
-    err_test!(parse_returns_err_if_start_close_parens, vec![Token::CloseParenthesis], UnexpectedTokenError);
-    err_test!(parse_returns_err_if_start_quote, vec![Token::Quote], UnexpectedTokenError);
-    err_test!(parse_returns_err_if_start_comma, vec![Token::Comma], UnexpectedTokenError);
-    err_test!(parse_returns_err_if_start_colon, vec![Token::Colon], UnexpectedTokenError);
+    err_test!(parse_returns_err_empty_input, "", UnexpectedEndOfInput);
+    err_test!(parse_returns_err_if_start_close_curly_brace, "}", UnexpectedToken);
+    err_test!(parse_returns_err_if_start_close_square_brace, "]", UnexpectedToken);
+    err_test!(parse_returns_err_if_start_open_parens, "(", UnexpectedToken);
+    err_test!(parse_returns_err_if_start_close_parens, ")", UnexpectedToken);
+    // A quote now starts a top-level string, which is unterminated.
+    err_test!(parse_returns_err_if_only_quote, "\"", UnexpectedEndOfInput);
+    err_test!(parse_returns_err_if_start_comma, ",", UnexpectedToken);
+    err_test!(parse_returns_err_if_start_colon, ":", UnexpectedToken);
 
     // #[test]
     // fn parse_returns_err_empty_tokens() {
     //     let tokens = Vec::new();
     //
     //     match parse(tokens) {
-    //         Err(e) => assert_err!(e, UnexpectedEndOfInputError),
+    //         Err(e) => assert_err!(e, UnexpectedEndOfInput),
     //         _ => assert!(false)
     //     };
     // }
 
+    macro_rules! string_theory {
+        ($name:ident, $input:literal, $expected:expr) => {
+            #[test]
+            fn $name() {
+                let mut v = JsonExpressionVisitor::new();
+                let mut expr = parse(concat!("{\"a\": \"", $input, "\"}")).unwrap();
+                expr.accept(&mut v);
+
+                let mut expected = String::from("{\"a\": ");
+                write_json_string($expected, &mut expected);
+                expected.push('}');
+
+                assert_eq!(expected, v.get_json());
+            }
+        }
+    }
+
+    string_theory!(string_with_punctuation, "a.b@c-d!?#%&*+=;<>[](){}|~^`'", "a.b@c-d!?#%&*+=;<>[](){}|~^`'");
+    string_theory!(string_with_simple_escapes, r#"\"\\\/\b\f\n\r\t"#, "\"\\/\u{8}\u{c}\n\r\t");
+    string_theory!(string_with_unicode_escape, r#"\u00e9\u4E2D"#, "é中");
+    string_theory!(string_with_surrogate_pair, r#"\ud83d\ude00"#, "😀");
+    string_theory!(string_with_non_ascii, "grüße 東京", "grüße 東京");
+    string_theory!(string_with_tag, r#"{{pick("a")}} {{pick(\"b\")}}"#, r#"{{pick("a")}} {{pick("b")}}"#);
+
+    macro_rules! name_theory {
+        ($name:ident, $input:literal, $expected:literal) => {
+            #[test]
+            fn $name() {
+                let mut v = JsonExpressionVisitor::new();
+                let mut expr = parse(concat!("{", $input, ": 1}")).unwrap();
+                expr.accept(&mut v);
+
+                assert_eq!(concat!("{", $expected, ": 1}"), v.get_json());
+            }
+        }
+    }
+
+    name_theory!(name_with_dash, r#""first-name""#, r#""first-name""#);
+    name_theory!(name_with_dollar, r#""$id""#, r#""$id""#);
+    name_theory!(name_with_at, r#""@type""#, r#""@type""#);
+    name_theory!(name_with_dot, r#""user.email""#, r#""user.email""#);
+    name_theory!(name_with_leading_digit, r#""2fa""#, r#""2fa""#);
+    name_theory!(name_with_spaces, r#"" a b ""#, r#"" a b ""#);
+    name_theory!(name_empty, "\"\"", "\"\"");
+    name_theory!(name_with_escapes, r#""q\"\\\né\t""#, r#""q\"\\\né\t""#);
+    name_theory!(name_with_escaped_braces, r#""\{{x}}""#, r#""\{{x}}""#);
+    err_test!(name_with_bad_escape, "{\"a\\x\": 1}", UnexpectedToken);
+
+    macro_rules! string_err_theory {
+        ($name:ident, $input:literal, $t:ident) => {
+            err_test!($name, concat!("{\"a\": \"", $input, "\"}"), $t);
+        }
+    }
+
+    string_err_theory!(string_with_invalid_escape, r#"\x"#, UnexpectedToken);
+    string_err_theory!(string_with_short_unicode_escape, r#"\u12"#, UnexpectedToken);
+    string_err_theory!(string_with_lone_low_surrogate, r#"\udc00"#, UnexpectedToken);
+    string_err_theory!(string_with_unpaired_high_surrogate, r#"\ud83dx"#, UnexpectedToken);
+    string_err_theory!(string_with_raw_newline, "\n", UnexpectedToken);
+    err_test!(string_unterminated, "{\"a\": \"abc", UnexpectedEndOfInput);
+
+    macro_rules! json_theory {
+        ($name:ident, $input:literal, $expected:literal) => {
+            #[test]
+            fn $name() {
+                let mut v = JsonExpressionVisitor::new();
+                let mut expr = parse($input).unwrap();
+                expr.accept(&mut v);
+
+                assert_eq!($expected, v.get_json());
+            }
+        }
+    }
+
+    json_theory!(template_round_trips, "[\"id-{{ guid }}-{{$n}}\", \"{{f(1, s=\\\"x\\\")}}\"]",
+        "[\"id-{{guid()}}-{{$n}}\",\"{{f(1, s=\\\"x\\\")}}\"]");
+    json_theory!(escaped_braces_round_trip, "\"a \\{{b}} {c}\"", "\"a \\{{b}} {c}\"");
+
+    err_test!(template_unclosed, "[\"{{f()\"]", UnexpectedEndOfInput);
+    err_test!(template_bad_argument, "\"{{f(x)}}\"", UnexpectedToken);
+    err_test!(template_variable_with_args, "\"{{$x()}}\"", UnexpectedToken);
+
+    #[test]
+    fn template_name_is_an_error() {
+        assert!(matches!(parse("{\"{{a}}\": 1}"), Err(ParseError::UnexpectedToken { .. })));
+    }
+
+    json_theory!(number_integer, "{\"a\": -123}", "{\"a\": -123}");
+    json_theory!(number_zero, "{\"a\": 0}", "{\"a\": 0}");
+    json_theory!(number_negative_zero, "{\"a\": -0}", "{\"a\": -0}");
+    json_theory!(number_fraction_keeps_lexeme, "{\"a\": 1.50}", "{\"a\": 1.50}");
+    json_theory!(number_exponent, "{\"a\": -2.5E+10,\"b\": 1e-3}", "{\"a\": -2.5E+10,\"b\": 1e-3}");
+    json_theory!(number_followed_by_whitespace, "{\"a\": 12 , \"b\": 3\n}", "{\"a\": 12,\"b\": 3}");
+    json_theory!(number_i64_bounds, "{\"a\": 9223372036854775807,\"b\": -9223372036854775808}",
+        "{\"a\": 9223372036854775807,\"b\": -9223372036854775808}");
+
+    macro_rules! number_err_theory {
+        ($name:ident, $input:literal, $t:ident) => {
+            err_test!($name, concat!("{\"a\": ", $input, "}"), $t);
+        }
+    }
+
+    number_err_theory!(number_leading_zero, "012", UnexpectedToken);
+    number_err_theory!(number_trailing_dot, "1.", UnexpectedToken);
+    number_err_theory!(number_leading_dot, ".5", UnexpectedToken);
+    number_err_theory!(number_empty_exponent, "1e", UnexpectedToken);
+    number_err_theory!(number_lone_minus, "-", UnexpectedToken);
+    number_err_theory!(number_trailing_letters, "12abc", UnexpectedToken);
+    number_err_theory!(number_overflow, "9223372036854775808", NumberOutOfRange);
+    number_err_theory!(number_negative_overflow, "-9223372036854775809", NumberOutOfRange);
+    number_err_theory!(number_float_overflow, "1e400", NumberOutOfRange);
+    number_err_theory!(number_negative_float_overflow, "-1.5E+309", NumberOutOfRange);
+
+    json_theory!(number_float_underflow_is_zero, "[1e-400]", "[1e-400]");
+
+    #[test]
+    fn number_float_overflow_message() {
+        let e = parse("[1e400]").unwrap_err();
+
+        assert!(e.render("[1e400]").contains("Number 1e400 does not fit in a 64 bit float"), "{}", e.render("[1e400]"));
+    }
+
+    #[test]
+    fn number_float_overflow_is_kept_with_arbitrary_precision() {
+        let options = ParseOptions { arbitrary_precision: true, ..ParseOptions::default() };
+
+        assert!(parse_with_options("[1e400]", &options).is_ok());
+    }
+
+    json_theory!(booleans_and_null, "{\"a\": true,\"b\": false , \"c\": null}", "{\"a\": true,\"b\": false,\"c\": null}");
+    json_theory!(nested_object, "{\"a\": {\"b\": {\"c\": 1}}}", "{\"a\": {\"b\": {\"c\": 1}}}");
+    json_theory!(empty_array, "[]", "[]");
+    json_theory!(array_of_values, "[1, \"two\", true, null, 2.5]", "[1,\"two\",true,null,2.5]");
+    json_theory!(nested_arrays_and_objects, "{\"a\": [[1, 2], [], {\"b\": [{}]}]}", "{\"a\": [[1,2],[],{\"b\": [{}]}]}");
+
+    err_test!(keyword_misspelled, "{\"a\": nul}", UnexpectedToken);
+    err_test!(keyword_runs_on, "{\"a\": truex}", UnexpectedToken);
+    err_test!(array_unterminated, "[1, 2", UnexpectedEndOfInput);
+
+    fn parse_precise(input: &str) -> Result<String, ParseError> {
+        let options = ParseOptions { arbitrary_precision: true, ..ParseOptions::default() };
+        let mut v = JsonExpressionVisitor::new();
+        parse_with_options(input, &options)?.accept(&mut v);
+
+        Ok(String::from(v.get_json()))
+    }
+
+    #[test]
+    fn arbitrary_precision_keeps_numbers_exact() {
+        let input = "{\"a\": 123456789012345678901234567890,\"b\": -0.100000000000000000000001,\"c\": 1E+400}";
+
+        assert_eq!(parse_precise(input).unwrap(), input);
+    }
+
+    #[test]
+    fn arbitrary_precision_still_validates_numbers() {
+        assert!(parse_precise("{\"a\": 012}").is_err());
+        assert!(parse_precise("{\"a\": 1.}").is_err());
+    }
+
+    #[test]
+    fn parse_returns_err_if_not_a_value() {
+        match parse(" ,") {
+            Err(e) => assert_err!(e, UnexpectedToken),
+            _ => panic!("expected an error")
+        }
+    }
+
+    json_theory!(top_level_string, " \"hello\" ", "\"hello\"");
+    json_theory!(top_level_number, "-1.5e3\n", "-1.5e3");
+    json_theory!(top_level_null, "null", "null");
+
+    err_test!(empty_input, "  \n", UnexpectedEndOfInput);
+    err_test!(trailing_value, "{} {}", UnexpectedToken);
+
+    #[test]
+    fn parse_many_reads_each_document() {
+        let documents = parse_many("{\"a\": 1}\n[2]\n\n3 \"four\"true\n").unwrap();
+
+        let json: Vec<String> = documents.into_iter().map(|mut expr| {
+            let mut v = JsonExpressionVisitor::new();
+            expr.accept(&mut v);
+            String::from(v.get_json())
+        }).collect();
+
+        assert_eq!(json, vec!["{\"a\": 1}", "[2]", "3", "\"four\"", "true"]);
+    }
+
+    #[test]
+    fn parse_many_accepts_empty_input() {
+        assert!(parse_many(" \n ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_many_reports_bad_document() {
+        match parse_many("{}\n{\"a\" 1}") {
+            Err(e) => assert_err!(e, UnexpectedToken),
+            _ => panic!("expected an error")
+        }
+    }
+
+    #[test]
+    fn error_has_code_span_and_expected_tokens() {
+        let e = parse("{\"é\": ,}").unwrap_err();
+
+        assert_eq!(e.code(), "E0001");
+        assert_eq!(e.span(), Span { start: 7, end: 8, line: 1, column: 7 });
+        assert_eq!(e.expected(), ["a value"]);
+    }
+
+    #[test]
+    fn end_of_input_span_is_empty_at_end() {
+        let e = parse("[1, 2").unwrap_err();
+
+        assert_eq!(e.code(), "E0002");
+        assert_eq!(e.span(), Span { start: 5, end: 5, line: 1, column: 6 });
+    }
+
+    #[test]
+    fn number_out_of_range_spans_the_number() {
+        let e = parse("[-9223372036854775809]").unwrap_err();
+
+        assert_eq!(e.code(), "E0003");
+        assert_eq!(e.span(), Span { start: 1, end: 21, line: 1, column: 2 });
+    }
+
+    #[test]
+    fn render_underlines_the_span_in_its_line() {
+        let source = "{\n  \"a\": 1,\n  \"b\": 12345678901234567890\n}";
+        let e = parse(source).unwrap_err();
+
+        assert_eq!(e.render(source), concat!(
+            "error[E0003]: Number 12345678901234567890 does not fit in a 64 bit integer\n",
+            "  --> line 3, column 8\n",
+            "  |\n",
+            "3 |   \"b\": 12345678901234567890\n",
+            "  |        ^^^^^^^^^^^^^^^^^^^^\n"));
+    }
+
+    fn recover_json(input: &str) -> (String, Vec<&'static str>) {
+        let (mut expr, errors, _) = parse_recovering(input, &ParseOptions::default());
+        let mut v = JsonExpressionVisitor::new();
+        expr.accept(&mut v);
+
+        (String::from(v.get_json()), errors.iter().map(ParseError::code).collect())
+    }
+
+    #[test]
+    fn recovering_reports_every_error() {
+        let (json, errors) = recover_json("{\"a\": , \"b\": tru, \"c\": 1, 5, \"d\": [1, :, 2]}");
+
+        assert_eq!(json, "{\"a\": ,\"b\": ,\"c\": 1,,\"d\": [1,,2]}");
+        assert_eq!(errors, vec!["E0001"; 4]);
+    }
+
+    #[test]
+    fn recovering_resumes_after_mismatched_close() {
+        let (json, errors) = recover_json("{\"a\": [1, 2}");
+
+        assert_eq!(json, "{\"a\": [1,2]}");
+        assert_eq!(errors, vec!["E0001"]);
+    }
+
+    #[test]
+    fn recovering_reports_end_of_input_once() {
+        let (json, errors) = recover_json("{\"a\": [1, {\"b\": ");
+
+        assert_eq!(json, "{\"a\": [1,{\"b\": }]}");
+        assert_eq!(errors, vec!["E0002"]);
+    }
+
+    #[test]
+    fn recovering_valid_input_has_no_errors() {
+        let (json, errors) = recover_json("[{\"a\": null}]");
+
+        assert_eq!(json, "[{\"a\": null}]");
+        assert!(errors.is_empty());
+    }
+
+    /// Records the span of every name, value, object and array.
+    #[derive(Default)]
+    struct SpanVisitor {
+        spans: Vec<(String, Span)>,
+    }
+
+    impl ExpressionVisitor for SpanVisitor {
+        fn visit_array(&mut self, expr: &mut JsonArrayExpression) {
+            self.spans.push((String::from("array"), expr.span()));
+            expr.expressions.iter_mut().for_each(|e| e.accept(self));
+        }
+
+        fn visit_object(&mut self, expr: &mut JsonObjectExpression) {
+            self.spans.push((String::from("object"), expr.span()));
+            expr.expressions.iter_mut().for_each(|e| e.accept(self));
+        }
+
+        fn visit_name(&mut self, expr: &mut NameExpression) {
+            self.spans.push((expr.name.clone(), expr.span()));
+        }
+
+        fn visit_property_assignment(&mut self, expr: &mut PropertyAssignmentExpression) {
+            self.spans.push((String::from("property"), expr.span()));
+            expr.name.accept(self);
+            expr.value.accept(self);
+        }
+
+        fn visit_comma_expression(&mut self, _: &CommaExpression) {}
+
+        fn visit_value(&mut self, expr: &mut ValueExpression) {
+            self.spans.push((format!("{:?}", expr.value), expr.span()));
+        }
+
+        fn visit_template(&mut self, expr: &mut TemplateExpression) {
+            self.spans.push((String::from("template"), expr.span()));
+        }
+
+        fn visit_whitespace_expression(&mut self, _: &WhitespaceExpression) {}
+
+        fn visit_comment(&mut self, _: &CommentExpression) {}
+
+        fn visit_error(&mut self, _: &ErrorExpression) {}
+    }
+
+    #[test]
+    fn nodes_have_spans() {
+        let mut v = SpanVisitor::default();
+        parse("{\n  \"ñ\": [1, -2.5],\n  \"b\": null\n}").unwrap().accept(&mut v);
+
+        let span = |start, end, line, column| Span { start, end, line, column };
+        assert_eq!(v.spans, vec![
+            (String::from("object"), span(0, 34, 1, 1)),
+            (String::from("property"), span(4, 19, 2, 3)),
+            (String::from("ñ"), span(4, 8, 2, 3)),
+            (String::from("array"), span(10, 19, 2, 8)),
+            (String::from("Number(1)"), span(11, 12, 2, 9)),
+            (String::from("Float { value: -2.5, lexeme: \"-2.5\" }"), span(14, 18, 2, 12)),
+            (String::from("property"), span(23, 32, 3, 3)),
+            (String::from("b"), span(23, 26, 3, 3)),
+            (String::from("Null"), span(28, 32, 3, 8)),
+        ]);
+    }
+
+    err_test!(trailing_comma_in_object, "{\"a\": 1,}", UnexpectedToken);
+    err_test!(trailing_comma_in_array, "[1, 2, ]", UnexpectedToken);
+    err_test!(leading_comma_in_object, "{, \"a\": 1}", UnexpectedToken);
+    err_test!(missing_comma_in_object, "{\"a\": 1 \"b\": 2}", UnexpectedToken);
+    err_test!(missing_comma_in_array, "[1 2]", UnexpectedToken);
+    err_test!(double_comma_in_array, "[1,,2]", UnexpectedToken);
+    err_test!(comment_in_strict_mode, "// a\n{}", UnexpectedToken);
+
+    fn parse_lenient(input: &str) -> Result<String, ParseError> {
+        let options = ParseOptions { lenient: true, ..ParseOptions::default() };
+        let mut v = JsonExpressionVisitor::new();
+        parse_with_options(input, &options)?.accept(&mut v);
+
+        Ok(String::from(v.get_json()))
+    }
+
+    macro_rules! lenient_theory {
+        ($name:ident, $input:literal, $expected:literal) => {
+            #[test]
+            fn $name() {
+                assert_eq!(parse_lenient($input).unwrap(), $expected);
+            }
+        }
+    }
+
+    lenient_theory!(lenient_line_comments, "// header\n{\"a\": 1, // one\n\"b\": 2}// end", "{\"a\": 1,\"b\": 2}");
+    lenient_theory!(lenient_block_comments, "/* a\n * b */[1, /* two */ 2]", "[1,2]");
+    lenient_theory!(lenient_trailing_commas, "{\"a\": [1, 2,], \"b\": {\"c\": 3,},}", "{\"a\": [1,2],\"b\": {\"c\": 3}}");
+    lenient_theory!(lenient_unquoted_keys, "{a: 1, $b_2 : 2, _c: 3}", "{\"a\": 1,\"$b_2\": 2,\"_c\": 3}");
+    lenient_theory!(lenient_literal_keys, "{true: 1, false: 2, null: 3, nullable: 4}", "{\"true\": 1,\"false\": 2,\"null\": 3,\"nullable\": 4}");
+    lenient_theory!(lenient_single_quotes, "{'a': 'it\\'s \"x\"'}", "{\"a\": \"it's \\\"x\\\"\"}");
+    lenient_theory!(lenient_hex_numbers, "[0x1F, -0Xff, 0x7fffffffffffffff]", "[31,-255,9223372036854775807]");
+    lenient_theory!(lenient_slash_in_string_is_not_comment, "{\"a\": \"http://x\"}", "{\"a\": \"http://x\"}");
+
+    #[test]
+    fn lenient_rejects_bad_input() {
+        assert!(matches!(parse_lenient("[0x]"), Err(ParseError::UnexpectedToken { .. })));
+        assert!(matches!(parse_lenient("[0x10000000000000000]"), Err(ParseError::NumberOutOfRange { .. })));
+        assert!(matches!(parse_lenient("/* open"), Err(ParseError::UnexpectedEndOfInput { .. })));
+        assert!(matches!(parse_lenient("[1,,]"), Err(ParseError::UnexpectedToken { .. })));
+        assert!(matches!(parse_lenient("{a b: 1}"), Err(ParseError::UnexpectedToken { .. })));
+    }
+
+    #[test]
+    fn literal_keys_need_lenient_mode() {
+        assert_eq!(parse("{true: 1}").unwrap_err().expected(), ["'\"'", "'}'"]);
+        assert_eq!(parse_lenient("{1: 1}").unwrap_err().expected(), ["'\"'", "identifier", "'}'"]);
+        assert_eq!(round_trip("{ null :1}", true), "{ null :1}");
+    }
+
+    #[test]
+    fn parse_reader_reads_incrementally() {
+        let input = "{\"a\": [1, \"é\"]}\n";
+        let mut v = JsonExpressionVisitor::new();
+        parse_reader(input.as_bytes(), &ParseOptions::default()).unwrap().accept(&mut v);
+
+        assert_eq!(v.get_json(), "{\"a\": [1,\"é\"]}");
+    }
+
+    #[test]
+    fn parse_many_reader_reads_each_document() {
+        let documents = parse_many_reader(&b"{}\n[]\n1"[..], &ParseOptions::default()).unwrap();
+
+        assert_eq!(documents.len(), 3);
+    }
+
+    #[test]
+    fn parse_reader_reports_invalid_utf8() {
+        let e = parse_reader(&b"{\"a\": \"x\xFF\"}"[..], &ParseOptions::default()).unwrap_err();
+
+        assert_eq!(e.code(), "E0005");
+        assert_eq!((e.span().start, e.span().column), (8, 9));
+    }
+
     #[test]
-    fn parse_returns_err_if_not_start_object_or_array() {
-        let tokens = vec![Token::Whitespace(' ')];
+    fn parse_reader_reports_invalid_utf8_after_document() {
+        let e = parse_reader(&b"{} \xC3"[..], &ParseOptions::default()).unwrap_err();
 
-        match parse(tokens) {
-            Err(e) => assert_err!(e, UnexpectedTokenError),
-            _ => assert!(false)
+        assert_eq!(e.code(), "E0005");
+    }
+
+    struct FailingReader<'a>(&'a [u8]);
+
+    impl std::io::Read for FailingReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            match self.0.read(buf)? {
+                0 => Err(std::io::Error::other("connection reset")),
+                n => Ok(n),
+            }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parse_reader_reports_io_errors() {
+        let e = parse_reader(FailingReader(b"[1, 2"), &ParseOptions::default()).unwrap_err();
+
+        assert_eq!(e.code(), "E0004");
+        assert_eq!(e.to_string(), "Error reading input. connection reset. Line 1. Column 6");
+    }
+
+    fn round_trip(input: &str, lenient: bool) -> String {
+        let options = ParseOptions { lenient, ..ParseOptions::default() };
+        let mut v = SourceExpressionVisitor::new();
+        parse_lossless(input, &options).unwrap().accept(&mut v);
+
+        v.source
+    }
+
+    macro_rules! lossless_theory {
+        ($name:ident, $input:expr) => {
+            #[test]
+            fn $name() {
+                assert_eq!(round_trip($input, false), $input);
+                assert_eq!(round_trip($input, true), $input);
+            }
+        }
+    }
+
+    lossless_theory!(lossless_compact, r#"{"a":[1,2.50,-0,true,null],"b":{}}"#);
+    lossless_theory!(lossless_whitespace_everywhere, " \r\n{ \"a\"\t:\n [ 1 ,\n 2 ] , \"b\" : { } }\n\n");
+    lossless_theory!(lossless_escapes, r#"["\u00e9\n", "\/", "\{{x}}"]"#);
+    lossless_theory!(lossless_names, r#"{"\u0061" : 1, "first-name": 2}"#);
+    lossless_theory!(lossless_big_numbers, "[1.0e+10, 123456789012345678901234567890.5]");
+    lossless_theory!(lossless_templates, r#"{"id": "x-{{ guid }}-{{pick( \"colors\" , n = 2 )}}"}"#);
+    lossless_theory!(lossless_scalar, "  \"just a string\"  ");
+
+    #[test]
+    fn lossless_lenient_keeps_comments_and_trailing_commas() {
+        let input = "// header\n{a: 'x', /* b */ b : [0x1F, 2,], // end\n}/* done */";
+        assert_eq!(round_trip(input, true), input);
+
+        let mut v = JsonExpressionVisitor::new();
+        let options = ParseOptions { lenient: true, ..ParseOptions::default() };
+        parse_lossless(input, &options).unwrap().value.accept(&mut v);
+        assert_eq!(v.get_json(), "{\"a\": \"x\",  \"b\": [31, 2] \n}");
+    }
+
+    #[test]
+    fn nodes_can_be_matched() {
+        let members = match parse(r#"{"a": [1, "{{guid}}"]}"#).unwrap() {
+            Node::Object(object) => object.expressions,
+            node => panic!("{:?}", node),
+        };
+
+        let elements = match members.as_slice() {
+            [Node::Property(property)] if property.name.name == "a" => match property.value.as_ref() {
+                Node::Array(array) => &array.expressions,
+                node => panic!("{:?}", node),
+            },
+            nodes => panic!("{:?}", nodes),
+        };
+
+        assert!(matches!(elements.as_slice(), [
+            Node::Value(ValueExpression { value: JsonValue::Number(1), .. }),
+            Node::Comma(_),
+            Node::Template(_),
+        ]));
+    }
+
+    #[test]
+    fn lossless_rejects_bad_input() {
+        assert!(parse_lossless("{\"a\": 1} x", &ParseOptions::default()).is_err());
+        assert!(parse_lossless("[1,]", &ParseOptions::default()).is_err());
+    }
+
+    struct SetValues;
+
+    impl ExpressionVisitor for SetValues {
+        fn visit_array(&mut self, expr: &mut JsonArrayExpression) {
+            expr.expressions.iter_mut().for_each(|e| e.accept(self));
+        }
+        fn visit_object(&mut self, expr: &mut JsonObjectExpression) {
+            expr.expressions.iter_mut().for_each(|e| e.accept(self));
+        }
+        fn visit_name(&mut self, _: &mut NameExpression) {}
+        fn visit_property_assignment(&mut self, expr: &mut PropertyAssignmentExpression) {
+            expr.value.accept(self);
+        }
+        fn visit_comma_expression(&mut self, _: &CommaExpression) {}
+        fn visit_value(&mut self, expr: &mut ValueExpression) {
+            if expr.value == JsonValue::Number(2) {
+                expr.set_value(JsonValue::String(String::from("two")));
+            }
+        }
+        fn visit_template(&mut self, _: &mut TemplateExpression) {}
+        fn visit_whitespace_expression(&mut self, _: &WhitespaceExpression) {}
+        fn visit_comment(&mut self, _: &CommentExpression) {}
+        fn visit_error(&mut self, _: &ErrorExpression) {}
+    }
+
+    #[test]
+    fn lossless_edits_only_change_what_was_edited() {
+        let options = ParseOptions { lenient: true, ..ParseOptions::default() };
+        let mut document = parse_lossless("{\n  // keep\n  'a' : 1.0,\n  b: [ 2 , 0x2 ],\n}\n", &options).unwrap();
+        document.accept(&mut SetValues);
+
+        let mut v = SourceExpressionVisitor::new();
+        document.accept(&mut v);
+        assert_eq!(v.source, "{\n  // keep\n  'a' : 1.0,\n  b: [ \"two\" , \"two\" ],\n}\n");
+    }
+
+    fn parse_keys(input: &str, duplicate_keys: DuplicateKeys) -> Result<(String, Vec<ParseError>), ParseError> {
+        let options = ParseOptions { duplicate_keys, ..ParseOptions::default() };
+        let (mut expr, warnings) = parse_with_warnings(input, &options)?;
+        let mut v = JsonExpressionVisitor::new();
+        expr.accept(&mut v);
+
+        Ok((String::from(v.get_json()), warnings))
+    }
+
+    const DUPLICATES: &str = "{\"a\": 1, \"b\": 2, \"a\": 3}";
+
+    #[test]
+    fn duplicate_keys_are_kept_with_a_warning_by_default() {
+        let (json, warnings) = parse_keys(DUPLICATES, DuplicateKeys::default()).unwrap();
+
+        assert_eq!(json, "{\"a\": 1,\"b\": 2,\"a\": 3}");
+        assert_eq!(warnings, vec![ParseError::DuplicateKey {
+            name: String::from("a"),
+            span: Span { start: 17, end: 20, line: 1, column: 18 },
+            first: Span { start: 1, end: 4, line: 1, column: 2 },
+        }]);
+    }
+
+    #[test]
+    fn duplicate_keys_can_be_errors() {
+        let e = parse_keys(DUPLICATES, DuplicateKeys::Error).unwrap_err();
+
+        assert_eq!(e.code(), "E0010");
+        assert_eq!(e.to_string(), "Duplicate key \"a\", first defined at line 1, column 2. Line 1. Column 18");
+        assert!(parse_keys("{\"a\": {\"a\": 1}, \"b\": [{\"a\": 2}, {\"a\": 3}]}", DuplicateKeys::Error).is_ok());
+    }
+
+    #[test]
+    fn first_duplicate_key_can_win() {
+        let (json, warnings) = parse_keys(DUPLICATES, DuplicateKeys::FirstWins).unwrap();
+
+        assert_eq!(json, "{\"a\": 1,\"b\": 2}");
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn last_duplicate_key_can_win() {
+        let (json, _) = parse_keys("{\"a\": 1, \"b\": 2, \"a\": 3, \"c\": 4, \"a\": 5}", DuplicateKeys::LastWins).unwrap();
+
+        assert_eq!(json, "{\"a\": 5,\"b\": 2,\"c\": 4}");
+    }
+
+    macro_rules! lossless_duplicates_theory {
+        ($name:ident, $policy:expr) => {
+            #[test]
+            fn $name() {
+                let input = "{\"a\": 1, \"b\": 2, \"a\": 3}";
+                let options = ParseOptions { duplicate_keys: $policy, ..ParseOptions::default() };
+                let (mut document, warnings) = parse_lossless_with_warnings(input, &options).unwrap();
+                let mut v = SourceExpressionVisitor::new();
+                document.accept(&mut v);
+
+                assert_eq!(v.source, input);
+                assert_eq!(warnings.len(), 1);
+            }
+        }
+    }
+
+    lossless_duplicates_theory!(lossless_keeps_duplicates_when_first_wins, DuplicateKeys::FirstWins);
+    lossless_duplicates_theory!(lossless_keeps_duplicates_when_last_wins, DuplicateKeys::LastWins);
+
+    #[test]
+    fn recovering_reports_duplicate_keys() {
+        let options = ParseOptions { duplicate_keys: DuplicateKeys::Error, ..ParseOptions::default() };
+        let (_, errors, warnings) = parse_recovering("{\"a\": 1, \"a\": 2, \"b\": }", &options);
+        assert_eq!(errors.iter().map(ParseError::code).collect::<Vec<_>>(), vec!["E0010", "E0001"]);
+        assert!(warnings.is_empty());
+
+        let (_, errors, warnings) = parse_recovering(DUPLICATES, &ParseOptions::default());
+        assert!(errors.is_empty());
+        assert!(warnings[0].render_warning(DUPLICATES).starts_with("warning[E0010]: Duplicate key \"a\""));
+    }
+
+    fn nested_arrays(depth: usize) -> String {
+        format!("{}{}", "[".repeat(depth), "]".repeat(depth))
+    }
+
+    #[test]
+    fn depth_is_limited_by_default() {
+        assert!(parse(&nested_arrays(DEFAULT_MAX_DEPTH)).is_ok());
+
+        let e = parse(&nested_arrays(DEFAULT_MAX_DEPTH + 1)).unwrap_err();
+        assert_eq!(e, ParseError::TooDeep { max: DEFAULT_MAX_DEPTH, span: Span { start: 128, end: 129, line: 1, column: 129 } });
+
+        // Fails instead of overflowing the stack.
+        assert_eq!(parse(&nested_arrays(100_000)).unwrap_err().code(), "E0006");
+    }
+
+    #[test]
+    fn depth_counts_objects_and_arrays() {
+        let options = ParseOptions { max_depth: Some(2), ..ParseOptions::default() };
+
+        assert!(parse_with_options("{\"a\": [1]}", &options).is_ok());
+        assert!(matches!(parse_with_options("{\"a\": [{}]}", &options), Err(ParseError::TooDeep { max: 2, .. })));
+        assert!(parse_with_options(&nested_arrays(DEFAULT_MAX_DEPTH + 1), &ParseOptions { max_depth: None, ..options }).is_ok());
+    }
+
+    #[test]
+    fn recovering_skips_values_that_are_too_deep() {
+        let options = ParseOptions { max_depth: Some(2), ..ParseOptions::default() };
+        let (mut expr, errors, _) = parse_recovering("[[[1, {}]], 2, [3]]", &options);
+        let mut v = JsonExpressionVisitor::new();
+        expr.accept(&mut v);
+
+        assert_eq!(errors.iter().map(ParseError::code).collect::<Vec<_>>(), vec!["E0006"]);
+        assert_eq!(v.get_json(), "[[],2,[3]]");
+    }
+
+    #[test]
+    fn tokens_are_limited() {
+        // Whitespace counts.
+        let options = ParseOptions { max_tokens: Some(6), ..ParseOptions::default() };
+
+        assert!(parse_with_options("[1, 2]", &options).is_ok());
+        let e = parse_with_options("[1, 2, 3]", &options).unwrap_err();
+        assert_eq!(e, ParseError::TooManyTokens { max: 6, span: Span { start: 6, end: 7, line: 1, column: 7 } });
+        assert_eq!(e.to_string(), "Input has more than 6 tokens. Line 1. Column 7");
+    }
+
+    #[test]
+    fn strings_are_limited() {
+        let options = ParseOptions { max_string_length: Some(3), ..ParseOptions::default() };
+
+        assert!(parse_with_options("{\"abc\": \"d\\u00e9\"}", &options).is_ok());
+        for input in ["[\"abcd\"]", "{\"abcd\": 1}", "\"é{{guid}}\"", "\"{{guid}}abcd\""].iter() {
+            let e = parse_with_options(input, &options).unwrap_err();
+            assert!(matches!(e, ParseError::StringTooLong { max: 3, .. }), "{}: {:?}", input, e);
+        }
+    }
+
+    /// A string of `len` bytes, read a byte at a time, counting what has been read.
+    struct LongString {
+        len: usize,
+        read: std::rc::Rc<std::cell::Cell<usize>>,
+    }
+
+    impl Read for LongString {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let offset = self.read.get();
+            if buf.is_empty() || offset > self.len + 2 {
+                return Ok(0);
+            }
+            buf[0] = match offset {
+                0 => b'[',
+                1 => b'"',
+                n if n == self.len + 2 => b'"',
+                _ => b'a',
+            };
+            self.read.set(offset + 1);
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn long_strings_are_not_read_from_a_reader() {
+        let options = ParseOptions { max_string_length: Some(1000), ..ParseOptions::default() };
+        let read = std::rc::Rc::new(std::cell::Cell::new(0));
+        let reader = LongString { len: 100_000_000, read: read.clone() };
+
+        let e = parse_reader(reader, &options).unwrap_err();
+
+        assert!(matches!(e, ParseError::StringTooLong { max: 1000, .. }), "{:?}", e);
+        // The opening bracket and quote, the bytes allowed and the one that broke the limit.
+        assert_eq!(read.get(), 2 + 1001);
+    }
+
+    #[test]
+    fn long_strings_end_lexing() {
+        let options = ParseOptions { max_string_length: Some(3), ..ParseOptions::default() };
+
+        for input in ["[\"ab\\u00e9\", 1]", "[\"{{f(\\\"abcd\\\")}}\"]", "[\"{{abcd}}\"]", "{abcd: 1}"].iter() {
+            let options = ParseOptions { lenient: true, ..options.clone() };
+            let (_, errors, _) = parse_recovering(input, &options);
+            assert!(matches!(errors.as_slice(), [ParseError::StringTooLong { max: 3, .. }]), "{}: {:?}", input, errors);
+        }
+        assert!(parse_with_options("[\"{{f(\\\"abc\\\", 1.25)}}\"]", &options).is_ok());
+    }
+
+    #[test]
+    fn object_members_are_limited() {
+        let options = ParseOptions { max_object_members: Some(2), ..ParseOptions::default() };
+
+        assert!(parse_with_options("{\"a\": {\"b\": 1, \"c\": 2}, \"d\": [3, 4, 5]}", &options).is_ok());
+        let e = parse_with_options("{\"a\": 1, \"b\": 2, \"c\": 3}", &options).unwrap_err();
+        assert_eq!(e, ParseError::TooManyMembers { max: 2, span: Span { start: 17, end: 20, line: 1, column: 18 } });
+    }
+}
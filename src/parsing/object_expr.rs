@@ -1,151 +1,255 @@
-use super::ParseContext;
+use super::{DuplicateKeys, ParseContext, ParseOptions};
 use crate::ast::{
-    Expression,
     JsonValue,
     comma::CommaExpression,
     json_object::JsonObjectExpression,
+    node::Node,
     property_assignment::PropertyAssignmentExpression,
 };
-use crate::tokens::Token;
-use super::errors::UnexpectedTokenError;
-use crate::parsing::{ParseResult, parse};
+use crate::tokens::{Literal, Punct, TokenKind};
+use super::errors::ParseError;
+use crate::parsing::ParseResult;
+use super::array_expr::parse_array_expr;
+use super::template_expr::parse_template_expr;
 use crate::ast::name::NameExpression;
 use crate::ast::value::ValueExpression;
-use crate::parsing::errors::UnexpectedEndOfInputError;
+use crate::ast::span::Span;
+use std::collections::HashMap;
+
+pub const MEMBER: &[&str] = &["'\"'", "'}'"];
+pub const LENIENT_MEMBER: &[&str] = &["'\"'", "identifier", "'}'"];
+pub const AFTER_MEMBER: &[&str] = &["','", "'}'"];
+pub const VALUE: &[&str] = &["a value"];
 
 pub fn parse_object_expr(ctx: &mut ParseContext) -> ParseResult {
+    let start = ctx.start();
     let mut jo =  JsonObjectExpression::new();
+    // Set at the start and after each comma, when a member may come next.
+    let mut expect_member = true;
+    let mut members = 0;
+    // Where each name's member is in `jo.expressions`, and the span of its name.
+    let mut keys = HashMap::new();
+    // Where the comma after the last member is in `jo.expressions`, and its span.
+    let mut comma = None;
 
     loop {
-        if ctx.advance() {
-            match ctx.current.as_ref().unwrap() {
-                Token::Whitespace(_) => continue,
-                Token::Quote => jo.add_expr(parse_assignment_expr(ctx)?),
-                Token::Comma => jo.add_expr(Box::new(CommaExpression::new())),
-                Token::CloseCurlyBrace => break,
-                t => return Err(Box::new(UnexpectedTokenError::new(ctx.line, ctx.pos, t.clone())))
-            };
-        } else {
+        for trivia in ctx.trivia() {
+            jo.add_expr(trivia);
+        }
+        let expected = if expect_member { member(&ctx.options) } else { AFTER_MEMBER };
 
+        if !ctx.advance() {
+            ctx.record(ctx.end_of_input(expected))?;
+            break;
         }
+
+        match ctx.kind() {
+            Some(TokenKind::Punct(Punct::Comma)) if !expect_member => {
+                let span = ctx.span_from(ctx.start());
+                comma = Some((jo.expressions.len(), span));
+                jo.add_expr(CommaExpression { span, trailing: false });
+                expect_member = true;
+            },
+            Some(TokenKind::Punct(Punct::CloseCurlyBrace)) => {
+                if let Some((index, span)) = comma.filter(|_| expect_member) {
+                    ctx.trailing_comma(&mut jo.expressions, index, span, &["'\"'"])?;
+                }
+                break;
+            },
+            // Closes an enclosing array, so when recovering this object ends here.
+            Some(TokenKind::Punct(Punct::CloseSquareBrace)) => {
+                ctx.record(ctx.unexpected(expected))?;
+                ctx.rewind();
+                break;
+            },
+            Some(kind) if starts_name(kind, &ctx.options) => {
+                if !expect_member {
+                    ctx.record(ctx.unexpected(AFTER_MEMBER))?;
+                }
+                members += 1;
+                if let Some(max) = ctx.options.max_object_members.filter(|&max| members == max + 1) {
+                    let span = ctx.current.as_ref().expect("there should be a current token").span;
+                    ctx.record(ParseError::TooManyMembers { max, span })?;
+                }
+                let after_comma = comma.take().filter(|_| expect_member).map(|(index, _)| index);
+                match parse_assignment_expr(ctx) {
+                    Ok((name, span, member)) => add_member(ctx, &mut jo, &mut keys, name, span, member, after_comma)?,
+                    Err(e) => jo.add_expr(ctx.recover(e)?),
+                }
+                expect_member = false;
+            },
+            _ => {
+                jo.add_expr(ctx.recover(ctx.unexpected(expected))?);
+                comma = None;
+                expect_member = false;
+            },
+        };
     }
 
-    Ok(Box::new(jo))
+    jo.span = ctx.span_from(start);
+    Ok(Node::Object(jo))
 }
 
-pub fn parse_assignment_expr(ctx: &mut ParseContext) -> ParseResult {
-    let name = parse_name(ctx)?;
-    let value = parse_value(ctx)?;
+/// What may start a member, which in lenient mode includes unquoted names.
+pub fn member(options: &ParseOptions) -> &'static [&'static str] {
+    if options.lenient { LENIENT_MEMBER } else { MEMBER }
+}
 
-    Ok(Box::new(PropertyAssignmentExpression::new(name, value)))
+/// Lenient mode also allows unquoted names, including `true`, `false` and `null`.
+pub fn starts_name(kind: &TokenKind, options: &ParseOptions) -> bool {
+    match kind {
+        TokenKind::String(_) => true,
+        TokenKind::Identifier(_) | TokenKind::Literal(_) => options.lenient,
+        _ => false,
+    }
 }
 
-pub fn parse_name(ctx: &mut ParseContext) -> ParseResult {
-    let mut name = String::new();
+/// Adds a member to `jo`, unless `ParseOptions::duplicate_keys` leaves it out. A lossless tree
+/// keeps every member, leaving the policy to whatever reads values from it. `after_comma` is
+/// where the comma before the member is.
+fn add_member(ctx: &mut ParseContext, jo: &mut JsonObjectExpression, keys: &mut HashMap<String, (usize, Span)>,
+              name: String, span: Span, member: PropertyAssignmentExpression, after_comma: Option<usize>) -> Result<(), ParseError> {
+    let (index, first) = match keys.get(&name) {
+        Some(&key) => key,
+        None => {
+            keys.insert(name, (jo.expressions.len(), span));
+            jo.add_expr(member);
+            return Ok(());
+        },
+    };
+
+    let e = ParseError::DuplicateKey { name, span, first };
+    match ctx.options.duplicate_keys {
+        DuplicateKeys::Error => ctx.record(e)?,
+        _ => ctx.warn(e),
+    }
 
-    let mut name_ended = false;
-    loop {
-        if ctx.advance() {
-            match ctx.current.as_ref().unwrap() {
-                Token::Character(c) => name.push(*c),
-                Token::Digit(d) => {
-                    if name.len() > 0 {
-                        name.push_str(format!("{}", *d).as_str())
-                    } else {
-                        return Err(Box::new(UnexpectedTokenError::new(ctx.line, ctx.pos, Token::Digit(*d))));
-                    }
-                },
-                Token::Quote => {
-                    name_ended = true;
-                },
-                Token::Colon => break,
-                Token::Whitespace(s) => {
-                    if !name_ended {
-                        return Err(Box::new(UnexpectedTokenError::new(ctx.line, ctx.pos, Token::Whitespace(*s))));
-                    }
-                },
-                t => return Err(Box::new(UnexpectedTokenError::new(ctx.line, ctx.pos, t.clone()))),
+    match ctx.options.duplicate_keys {
+        DuplicateKeys::Error | DuplicateKeys::Warn => jo.add_expr(member),
+        _ if ctx.lossless.is_some() => jo.add_expr(member),
+        policy => {
+            // The comma before the duplicate goes with it.
+            if let Some(index) = after_comma {
+                jo.expressions.truncate(index);
             }
-        } else {
-            return Err(Box::new(UnexpectedEndOfInputError::new(ctx.line, ctx.pos)));
-        }
+            if policy == DuplicateKeys::LastWins {
+                jo.expressions[index] = Node::Property(member);
+            }
+        },
     }
 
-    Ok(Box::new(NameExpression::new(name)))
+    Ok(())
 }
 
-pub fn parse_value(ctx: &mut ParseContext) -> ParseResult {
-    loop {
-        if ctx.advance() {
-            match ctx.current.as_ref().unwrap() {
-                Token::Whitespace(c) => continue,
-                Token::Quote => return Ok(parse_string(ctx)?),
-                Token::Digit(d) => return Ok(parse_number(ctx, *d, false)?),
-                Token::NegativeSign => {
-                    return if ctx.advance() {
-                        match ctx.current.as_ref().unwrap() {
-                            Token::Digit(d) => Ok(parse_number(ctx, *d, true)?),
-                            t => Err(Box::new(UnexpectedTokenError::new(ctx.line, ctx.pos, t.clone())))
-                        }
-                    } else {
-                        Err(Box::new(UnexpectedEndOfInputError::new(ctx.line, ctx.pos)))
-                    }
-                }
-                Token::Character('t') | Token::Character('f') => return Ok(parse_bool(ctx)?),
-                t => return Err(Box::new(UnexpectedTokenError::new(ctx.line, ctx.pos, t.clone())))
-            }
-        } else {
-            return Err(Box::new(UnexpectedEndOfInputError::new(ctx.line, ctx.pos)))
-        }
+/// Parses a member starting at its name, returning the name, its span and the member.
+pub fn parse_assignment_expr(ctx: &mut ParseContext) -> Result<(String, Span, PropertyAssignmentExpression), ParseError> {
+    let name = parse_name(ctx)?;
+    let (key, span) = (name.name.clone(), name.span);
+
+    let before_colon = ctx.trivia();
+    expect_colon(ctx)?;
+    let after_colon = ctx.trivia();
+    let value = parse_value(ctx).or_else(|e| ctx.recover(e))?;
+
+    let mut member = PropertyAssignmentExpression::new(name, value);
+    member.before_colon = before_colon;
+    member.after_colon = after_colon;
+
+    Ok((key, span, member))
+}
+
+/// Parses a name, the current token. A quoted name may hold anything a
+/// string can, except tags.
+pub fn parse_name(ctx: &mut ParseContext) -> Result<NameExpression, ParseError> {
+    let token = ctx.current.as_ref().expect("there should be a current token");
+    let span = token.span;
+
+    let name = match &token.kind {
+        TokenKind::String(s) | TokenKind::Identifier(s) => s.to_string(),
+        TokenKind::Literal(l) if ctx.options.lenient => String::from(l.as_str()),
+        _ => return Err(ctx.unexpected(member(&ctx.options))),
+    };
+
+    Ok(NameExpression { name, span, raw: ctx.raw(span) })
+}
+
+pub fn expect_colon(ctx: &mut ParseContext) -> Result<(), ParseError> {
+    if !ctx.advance() {
+        return Err(ctx.end_of_input(&["':'"]));
+    }
+
+    match ctx.kind() {
+        Some(TokenKind::Punct(Punct::Colon)) => Ok(()),
+        _ => Err(ctx.unexpected(&["':'"])),
     }
 }
 
-pub fn parse_string(ctx: &mut ParseContext) -> ParseResult {
-    let mut s = String::new();
+pub fn parse_value(ctx: &mut ParseContext) -> ParseResult {
+    ctx.skip_trivia();
 
-    loop {
-        if ctx.advance() {
-            match ctx.current.as_ref().unwrap() {
-                Token::Whitespace(ws) => s.push(*ws),
-                Token::Character(c) => s.push(*c),
-                Token::Digit(d) => s.push_str(format!("{}", *d).as_str()),
-                Token::Quote => return Ok(Box::new(ValueExpression::new(JsonValue::String(s)))),
-                t => return Err(Box::new(UnexpectedTokenError::new(ctx.pos, ctx.line, t.clone())))
-            }
-        } else {
-            return Err(Box::new(UnexpectedEndOfInputError::new(ctx.line, ctx.pos)));
-        }
+    if !ctx.advance() {
+        return Err(ctx.end_of_input(VALUE));
     }
+
+    let span = ctx.current.as_ref().expect("there should be a current token").span;
+    let value = match ctx.kind() {
+        Some(TokenKind::Punct(Punct::OpenCurlyBrace)) => return ctx.nested(parse_object_expr),
+        Some(TokenKind::Punct(Punct::OpenSquareBrace)) => return ctx.nested(parse_array_expr),
+        Some(TokenKind::TemplateHead(_)) => return parse_template_expr(ctx),
+        _ => scalar_value(ctx).unwrap_or_else(|| Err(ctx.unexpected(VALUE)))?,
+    };
+
+    Ok(Node::Value(ValueExpression { value, span, raw: ctx.raw(span) }))
 }
 
-pub fn parse_number(ctx: &mut ParseContext, first_digit: u8, is_neg: bool) -> ParseResult {
-    let mut num: i64 = first_digit as i64;
+/// The value of the current token when it is a string without tags, a number or a literal.
+pub fn scalar_value(ctx: &ParseContext) -> Option<Result<JsonValue, ParseError>> {
+    let token = ctx.current.as_ref().expect("there should be a current token");
+
+    Some(Ok(match &token.kind {
+        TokenKind::String(s) => JsonValue::String(s.to_string()),
+        TokenKind::Number(lexeme) => return Some(number_value(lexeme, token.span, &ctx.options)),
+        TokenKind::Literal(Literal::True) => JsonValue::Boolean(true),
+        TokenKind::Literal(Literal::False) => JsonValue::Boolean(false),
+        TokenKind::Literal(Literal::Null) => JsonValue::Null,
+        _ => return None,
+    }))
+}
 
-    loop {
-        if ctx.advance() {
-            match ctx.current.as_ref().unwrap() {
-                Token::Digit(d) => {
-                    num = (num * 10) + (*d as i64);
-                },
-                Token::Comma | Token::CloseCurlyBrace => {
-                    if !ctx.rewind() {
-                        panic!("We were unable to back up the token context");
-                    }
-
-                    if is_neg {
-                        num = num * -1;
-                    }
-
-                    return Ok(Box::new(ValueExpression::new(JsonValue::Number(num))))
-                }
-                t => return Err(Box::new(UnexpectedTokenError::new(ctx.line, ctx.pos, t.clone())))
-            }
+/// Converts a number the lexer has checked. Integers become `JsonValue::Number`, anything with a
+/// fraction or exponent becomes `JsonValue::Float`, which keeps the number as written. With
+/// `ParseOptions::arbitrary_precision` every number becomes `JsonValue::Decimal`. A float too
+/// large for an `f64`, such as `1e400`, is out of range rather than infinite.
+fn number_value(lexeme: &str, span: Span, options: &ParseOptions) -> Result<JsonValue, ParseError> {
+    let out_of_range = || ParseError::NumberOutOfRange { lexeme: String::from(lexeme), span };
+    let (sign, digits) = match lexeme.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", lexeme),
+    };
+
+    // Lenient mode hex integers such as `0x1F` or `-0xff`.
+    if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        let n = i64::from_str_radix(&format!("{}{}", sign, hex), 16).map_err(|_| out_of_range())?;
+
+        return Ok(if options.arbitrary_precision {
+            JsonValue::Decimal(n.to_string().parse().expect("an i64 is a valid number"))
         } else {
-            return Err(Box::new(UnexpectedEndOfInputError::new(ctx.line, ctx.pos)));
-        }
+            JsonValue::Number(n)
+        });
     }
-}
 
-pub fn parse_bool(ctx: &mut ParseContext) -> ParseResult {
-    unimplemented!()
-}
\ No newline at end of file
+    let is_int = !lexeme.contains(['.', 'e', 'E']);
+
+    // -0 has no i64 representation.
+    Ok(if options.arbitrary_precision {
+        JsonValue::Decimal(lexeme.parse().expect("lexeme should be a valid number"))
+    } else if is_int && lexeme != "-0" {
+        JsonValue::Number(lexeme.parse().map_err(|_| out_of_range())?)
+    } else {
+        let value: f64 = lexeme.parse().expect("lexeme should be a valid float");
+        if !value.is_finite() {
+            return Err(out_of_range());
+        }
+        JsonValue::Float { value, lexeme: String::from(lexeme) }
+    })
+}
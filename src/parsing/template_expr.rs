@@ -0,0 +1,140 @@
+use super::{ParseContext, ParseResult};
+use super::errors::ParseError;
+use super::object_expr::scalar_value;
+use crate::ast::{node::Node, template::TemplateExpression};
+use crate::tags::{Segment, Tag, TagArg};
+use crate::tokens::{Punct, TokenKind};
+
+const SEGMENT: &[&str] = &["a tag name", "'$'"];
+const ARGUMENT: &[&str] = &["a value", "'$'", "an argument name"];
+const AFTER_ARGUMENT: &[&str] = &["','", "')'"];
+const TAG_END: &[&str] = &["'}}'"];
+
+/// Parses a string with tags, starting at its `TemplateHead`, the current token.
+pub fn parse_template_expr(ctx: &mut ParseContext) -> ParseResult {
+    let start = ctx.start();
+    let segments = parse_segments(ctx)?;
+    let span = ctx.span_from(start);
+
+    Ok(Node::Template(TemplateExpression { segments, span, raw: ctx.raw(span) }))
+}
+
+/// Reads the text and tags of a template string up to its `TemplateTail`. Empty text is left
+/// out.
+pub fn parse_segments(ctx: &mut ParseContext) -> Result<Vec<Segment>, ParseError> {
+    let mut segments = Vec::new();
+    push_text(&mut segments, ctx.kind());
+
+    loop {
+        segments.push(parse_segment(ctx)?);
+
+        ctx.skip_trivia();
+        if !ctx.advance() {
+            return Err(ctx.end_of_input(TAG_END));
+        }
+
+        match ctx.kind() {
+            Some(TokenKind::TemplateMiddle(_)) => push_text(&mut segments, ctx.kind()),
+            Some(TokenKind::TemplateTail(_)) => {
+                push_text(&mut segments, ctx.kind());
+                return Ok(segments);
+            },
+            _ => return Err(ctx.unexpected(TAG_END)),
+        }
+    }
+}
+
+fn push_text(segments: &mut Vec<Segment>, kind: Option<&TokenKind>) {
+    if let Some(TokenKind::TemplateHead(s)) | Some(TokenKind::TemplateMiddle(s)) | Some(TokenKind::TemplateTail(s)) = kind {
+        if !s.is_empty() {
+            segments.push(Segment::Text(s.to_string()));
+        }
+    }
+}
+
+/// Parses the inside of `{{ }}`, a tag or a `$variable`.
+fn parse_segment(ctx: &mut ParseContext) -> Result<Segment, ParseError> {
+    next(ctx, SEGMENT)?;
+
+    match ctx.kind() {
+        Some(TokenKind::Punct(Punct::Dollar)) => parse_variable(ctx).map(Segment::Variable),
+        Some(TokenKind::Identifier(_)) => parse_tag(ctx).map(Segment::Tag),
+        _ => Err(ctx.unexpected(SEGMENT)),
+    }
+}
+
+/// Parses a tag such as `pick("colors")` or `guid`, starting at its name.
+fn parse_tag(ctx: &mut ParseContext) -> Result<Tag, ParseError> {
+    let mut tag = Tag { name: identifier(ctx, &["a tag name"])?, ..Tag::default() };
+
+    ctx.skip_trivia();
+    if !matches!(ctx.peek().map(|t| &t.kind), Some(TokenKind::Punct(Punct::OpenParenthesis))) {
+        return Ok(tag);
+    }
+    ctx.advance();
+
+    ctx.skip_trivia();
+    if matches!(ctx.peek().map(|t| &t.kind), Some(TokenKind::Punct(Punct::CloseParenthesis))) {
+        ctx.advance();
+        return Ok(tag);
+    }
+
+    loop {
+        next(ctx, ARGUMENT)?;
+
+        if let Some(TokenKind::Identifier(name)) = ctx.kind() {
+            let name = name.to_string();
+            next(ctx, &["'='"])?;
+            if ctx.kind() != Some(&TokenKind::Punct(Punct::Equals)) {
+                return Err(ctx.unexpected(&["'='"]));
+            }
+
+            next(ctx, ARGUMENT)?;
+            tag.named_args.push((name, parse_arg(ctx)?));
+        } else if tag.named_args.is_empty() {
+            tag.args.push(parse_arg(ctx)?);
+        } else {
+            // Positional arguments must come before named arguments.
+            return Err(ctx.unexpected(&["an argument name"]));
+        }
+
+        next(ctx, AFTER_ARGUMENT)?;
+        match ctx.kind() {
+            Some(TokenKind::Punct(Punct::CloseParenthesis)) => return Ok(tag),
+            Some(TokenKind::Punct(Punct::Comma)) => {},
+            _ => return Err(ctx.unexpected(AFTER_ARGUMENT)),
+        }
+    }
+}
+
+/// Parses an argument starting at the current token.
+/// Values are read as they are outside of tags, so numbers follow `ParseOptions` too.
+fn parse_arg(ctx: &mut ParseContext) -> Result<TagArg, ParseError> {
+    match ctx.kind() {
+        Some(TokenKind::Punct(Punct::Dollar)) => parse_variable(ctx).map(TagArg::Variable),
+        _ => scalar_value(ctx).unwrap_or_else(|| Err(ctx.unexpected(ARGUMENT))).map(TagArg::Value),
+    }
+}
+
+/// Parses the name of a variable whose `$` is the current token.
+fn parse_variable(ctx: &mut ParseContext) -> Result<String, ParseError> {
+    if !ctx.advance() {
+        return Err(ctx.end_of_input(&["a variable name"]));
+    }
+
+    identifier(ctx, &["a variable name"])
+}
+
+fn identifier(ctx: &ParseContext, expected: &[&'static str]) -> Result<String, ParseError> {
+    match ctx.kind() {
+        Some(TokenKind::Identifier(name)) => Ok(name.to_string()),
+        _ => Err(ctx.unexpected(expected)),
+    }
+}
+
+/// Advances past whitespace to the next token.
+fn next(ctx: &mut ParseContext, expected: &[&'static str]) -> Result<(), ParseError> {
+    ctx.skip_trivia();
+
+    if ctx.advance() { Ok(()) } else { Err(ctx.end_of_input(expected)) }
+}
@@ -0,0 +1,244 @@
+use super::errors::{DictionaryFormatError, DictionaryNotFoundError};
+use rand::{distributions::{Distribution, WeightedIndex}, Rng};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub struct Dictionary {
+    words: Vec<String>,
+    weights: Option<WeightedIndex<f64>>,
+}
+
+impl Dictionary {
+    pub fn new(words: Vec<String>) -> Dictionary {
+        Dictionary { words, weights: None }
+    }
+
+    /// Loads a word list. Files ending in `.csv` are read as `word[,weight]` records, anything
+    /// else is read as one word per line.
+    pub fn load(path: &Path) -> Result<Dictionary, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let text = text.trim_start_matches('\u{feff}');
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Dictionary::from_csv(path, text),
+            _ => Ok(Dictionary::from_lines(text)),
+        }
+    }
+
+    pub fn from_lines(text: &str) -> Dictionary {
+        Dictionary::new(text.lines()
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect())
+    }
+
+    pub fn from_csv(path: &Path, text: &str) -> Result<Dictionary, Box<dyn Error>> {
+        let mut words = Vec::new();
+        let mut weights = Vec::new();
+        let mut weighted = false;
+
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let format_err = |message: &str| DictionaryFormatError::new(path.to_path_buf(), i + 1, message);
+            let fields = split_csv_record(line).ok_or_else(|| format_err("Unterminated quoted field"))?;
+
+            let weight = match fields.as_slice() {
+                [_] => 1.0,
+                [_, w] => match w.trim().parse::<f64>() {
+                    Ok(w) if w.is_finite() && w >= 0.0 => {
+                        weighted = true;
+                        w
+                    },
+                    // A non numeric weight on the first record is a header row.
+                    Err(_) if words.is_empty() => continue,
+                    _ => return Err(Box::new(format_err("Weight must be a non-negative number"))),
+                },
+                _ => return Err(Box::new(format_err("Expected at most two columns"))),
+            };
+
+            words.push(fields[0].clone());
+            weights.push(weight);
+        }
+
+        let mut dictionary = Dictionary::new(words);
+        if weighted && !dictionary.words.is_empty() {
+            let index = WeightedIndex::new(weights)
+                .map_err(|_| DictionaryFormatError::in_file(path.to_path_buf(), "Weights must not all be zero"))?;
+            dictionary.weights = Some(index);
+        }
+
+        Ok(dictionary)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    pub fn choose<R: Rng>(&self, rng: &mut R) -> Option<&str> {
+        if self.words.is_empty() {
+            return None;
+        }
+
+        let i = match &self.weights {
+            Some(w) => w.sample(rng),
+            None => rng.gen_range(0, self.words.len()),
+        };
+
+        Some(&self.words[i])
+    }
+}
+
+fn split_csv_record(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    field.push('"');
+                    chars.next();
+                } else {
+                    quoted = false;
+                }
+            },
+            '"' if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            },
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+
+    if quoted {
+        return None;
+    }
+
+    fields.push(field);
+    Some(fields.into_iter().map(|f| String::from(f.trim())).collect())
+}
+
+/// Lazily loads and caches the dictionaries found in a directory.
+#[derive(Debug)]
+pub struct Dictionaries {
+    dir: Option<PathBuf>,
+    loaded: HashMap<String, Dictionary>,
+}
+
+impl Dictionaries {
+    pub fn new(dir: Option<PathBuf>) -> Dictionaries {
+        Dictionaries { dir, loaded: HashMap::new() }
+    }
+
+    pub fn get(&mut self, name: &str) -> Result<&Dictionary, Box<dyn Error>> {
+        if !self.loaded.contains_key(name) {
+            let path = self.resolve(name)
+                .ok_or_else(|| DictionaryNotFoundError::new(name, self.dir.clone()))?;
+            self.loaded.insert(String::from(name), Dictionary::load(&path)?);
+        }
+
+        Ok(&self.loaded[name])
+    }
+
+    fn resolve(&self, name: &str) -> Option<PathBuf> {
+        let dir = self.dir.as_ref()?;
+
+        // Names are plain file stems so a template can't reach outside the directory.
+        if name.is_empty() || name.starts_with('.') || name.contains(&['/', '\\'][..]) {
+            return None;
+        }
+
+        ["csv", "txt"].iter()
+            .map(|ext| dir.join(format!("{}.{}", name, ext)))
+            .chain(std::iter::once(dir.join(name)))
+            .find(|p| p.is_file())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, rngs::StdRng};
+
+    #[test]
+    fn from_lines_skips_blank_lines() {
+        let d = Dictionary::from_lines("red\n\n  green \r\nblue\n");
+
+        assert_eq!(d.words, vec!["red", "green", "blue"]);
+        assert!(d.weights.is_none());
+    }
+
+    #[test]
+    fn from_csv_reads_weights_and_skips_header() {
+        let d = Dictionary::from_csv(Path::new("c.csv"), "name,weight\nred,1\n\"navy, dark\",0\n").unwrap();
+
+        assert_eq!(d.words, vec!["red", "navy, dark"]);
+        assert!(d.weights.is_some());
+
+        let rng = &mut StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            assert_eq!(d.choose(rng), Some("red"));
+        }
+    }
+
+    #[test]
+    fn from_csv_without_weights_is_uniform() {
+        let d = Dictionary::from_csv(Path::new("c.csv"), "a\nb\n").unwrap();
+
+        assert_eq!(d.len(), 2);
+        assert!(d.weights.is_none());
+    }
+
+    #[test]
+    fn from_csv_rejects_negative_weight() {
+        assert!(Dictionary::from_csv(Path::new("c.csv"), "a,1\nb,-1\n").is_err());
+    }
+
+    #[test]
+    fn from_csv_rejects_unterminated_quote() {
+        assert!(Dictionary::from_csv(Path::new("c.csv"), "\"a,1\n").is_err());
+    }
+
+    #[test]
+    fn from_csv_errors_name_the_line() {
+        let e = Dictionary::from_csv(Path::new("c.csv"), "a,1\nb,-1\n").unwrap_err();
+
+        assert_eq!(e.to_string(), "Invalid dictionary c.csv. Weight must be a non-negative number. Line 2");
+    }
+
+    #[test]
+    fn from_csv_rejects_all_zero_weights_without_a_line() {
+        let e = Dictionary::from_csv(Path::new("c.csv"), "name,weight\na,0\nb,0\n").unwrap_err();
+
+        assert_eq!(e.to_string(), "Invalid dictionary c.csv. Weights must not all be zero");
+    }
+
+    #[test]
+    fn dictionaries_reject_names_outside_dir() {
+        let d = Dictionaries::new(Some(std::env::temp_dir()));
+
+        assert_eq!(d.resolve("../etc/passwd"), None);
+        assert_eq!(d.resolve(".hidden"), None);
+    }
+
+    #[test]
+    fn dictionaries_without_dir_is_not_found() {
+        let mut d = Dictionaries::new(None);
+
+        assert!(d.get("colors").unwrap_err().is::<DictionaryNotFoundError>());
+    }
+}
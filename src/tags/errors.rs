@@ -0,0 +1,115 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result};
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub struct UnknownTagError {
+    name: String,
+}
+
+impl UnknownTagError {
+    pub fn new(name: &str) -> UnknownTagError {
+        UnknownTagError { name: String::from(name) }
+    }
+}
+
+impl Error for UnknownTagError {}
+
+impl Display for UnknownTagError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Unknown tag {}", self.name)
+    }
+}
+
+#[derive(Debug)]
+pub struct UnknownLocaleError {
+    locale: String,
+}
+
+impl UnknownLocaleError {
+    pub fn new(locale: &str) -> UnknownLocaleError {
+        UnknownLocaleError { locale: String::from(locale) }
+    }
+}
+
+impl Error for UnknownLocaleError {}
+
+impl Display for UnknownLocaleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Unknown locale {}", self.locale)
+    }
+}
+
+#[derive(Debug)]
+pub struct TagArgumentError {
+    tag: String,
+    message: String,
+}
+
+impl TagArgumentError {
+    pub fn new(tag: &str, message: &str) -> TagArgumentError {
+        TagArgumentError { tag: String::from(tag), message: String::from(message) }
+    }
+}
+
+impl Error for TagArgumentError {}
+
+impl Display for TagArgumentError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Invalid arguments to {}. {}", self.tag, self.message)
+    }
+}
+
+#[derive(Debug)]
+pub struct DictionaryNotFoundError {
+    name: String,
+    dir: Option<PathBuf>,
+}
+
+impl DictionaryNotFoundError {
+    pub fn new(name: &str, dir: Option<PathBuf>) -> DictionaryNotFoundError {
+        DictionaryNotFoundError { name: String::from(name), dir }
+    }
+}
+
+impl Error for DictionaryNotFoundError {}
+
+impl Display for DictionaryNotFoundError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match &self.dir {
+            Some(dir) => write!(f, "Dictionary {} not found in {}", self.name, dir.display()),
+            None => write!(f, "Dictionary {} not found. No dictionary directory was given", self.name),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct DictionaryFormatError {
+    path: PathBuf,
+    /// `None` for a problem with the file as a whole.
+    line: Option<usize>,
+    message: String,
+}
+
+impl DictionaryFormatError {
+    pub fn new(path: PathBuf, line: usize, message: &str) -> DictionaryFormatError {
+        DictionaryFormatError { path, line: Some(line), message: String::from(message) }
+    }
+
+    /// An error with the file as a whole rather than one of its lines.
+    pub fn in_file(path: PathBuf, message: &str) -> DictionaryFormatError {
+        DictionaryFormatError { path, line: None, message: String::from(message) }
+    }
+}
+
+impl Error for DictionaryFormatError {}
+
+impl Display for DictionaryFormatError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Invalid dictionary {}. {}", self.path.display(), self.message)?;
+        match self.line {
+            Some(line) => write!(f, ". Line {}", line),
+            None => Ok(()),
+        }
+    }
+}
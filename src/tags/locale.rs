@@ -0,0 +1,86 @@
+/// Built-in data used by the name, address and phone tags. In formats `#` is replaced with a
+/// random digit.
+#[derive(Debug)]
+pub struct LocaleData {
+    pub name: &'static str,
+    pub first_names: &'static [&'static str],
+    pub last_names: &'static [&'static str],
+    pub cities: &'static [&'static str],
+    pub phone_formats: &'static [&'static str],
+    pub postal_code_formats: &'static [&'static str],
+}
+
+pub const DEFAULT_LOCALE: &str = "en_US";
+
+static LOCALES: &[LocaleData] = &[
+    LocaleData {
+        name: "en_US",
+        first_names: &[
+            "James", "Mary", "Robert", "Patricia", "John", "Jennifer", "Michael", "Linda", "David",
+            "Elizabeth", "William", "Barbara", "Richard", "Susan", "Joseph", "Jessica", "Thomas", "Sarah",
+        ],
+        last_names: &[
+            "Smith", "Johnson", "Williams", "Brown", "Jones", "Garcia", "Miller", "Davis", "Rodriguez",
+            "Martinez", "Hernandez", "Lopez", "Wilson", "Anderson", "Taylor", "Moore", "Jackson", "Martin",
+        ],
+        cities: &[
+            "New York", "Los Angeles", "Chicago", "Houston", "Phoenix", "Philadelphia", "San Antonio",
+            "San Diego", "Dallas", "Austin", "Seattle", "Denver", "Boston", "Portland",
+        ],
+        phone_formats: &["(###) ###-####", "###-###-####", "+1 ### ### ####"],
+        postal_code_formats: &["#####", "#####-####"],
+    },
+    LocaleData {
+        name: "de_DE",
+        first_names: &[
+            "Maximilian", "Sophie", "Alexander", "Marie", "Paul", "Hannah", "Lukas", "Emma", "Jonas",
+            "Lea", "Felix", "Lena", "Jürgen", "Jörg", "Björn", "Käthe", "Günther", "Ursula",
+        ],
+        last_names: &[
+            "Müller", "Schmidt", "Schneider", "Fischer", "Weber", "Meyer", "Wagner", "Becker", "Schulz",
+            "Hoffmann", "Schäfer", "Koch", "Bauer", "Richter", "Klein", "Wolf", "Schröder", "Neumann",
+        ],
+        cities: &[
+            "Berlin", "Hamburg", "München", "Köln", "Frankfurt am Main", "Stuttgart", "Düsseldorf",
+            "Leipzig", "Dortmund", "Essen", "Bremen", "Dresden", "Hannover", "Nürnberg",
+        ],
+        phone_formats: &["0### #######", "+49 ### #######", "030 ########"],
+        postal_code_formats: &["#####"],
+    },
+    LocaleData {
+        name: "ja_JP",
+        first_names: &[
+            "翔太", "陽菜", "蓮", "結衣", "大翔", "美咲", "悠真", "さくら", "湊", "葵", "健太", "愛子",
+        ],
+        last_names: &[
+            "佐藤", "鈴木", "高橋", "田中", "伊藤", "渡辺", "山本", "中村", "小林", "加藤", "吉田", "山田",
+        ],
+        cities: &[
+            "東京", "横浜", "大阪", "名古屋", "札幌", "福岡", "神戸", "京都", "川崎", "さいたま", "広島", "仙台",
+        ],
+        phone_formats: &["0#-####-####", "090-####-####", "080-####-####"],
+        postal_code_formats: &["###-####"],
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static LocaleData> {
+    // Accept both en_US and en-US.
+    LOCALES.iter().find(|l| l.name.replace('_', "-").eq_ignore_ascii_case(&name.replace('_', "-")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_accepts_either_separator() {
+        assert_eq!(find("de_DE").map(|l| l.name), Some("de_DE"));
+        assert_eq!(find("de-de").map(|l| l.name), Some("de_DE"));
+        assert!(find("xx_XX").is_none());
+    }
+
+    #[test]
+    fn default_locale_exists() {
+        assert!(find(DEFAULT_LOCALE).is_some());
+    }
+}
@@ -1,3 +1,401 @@
-pub fn rand(min: i32, max: i32) -> i32 {
-    Rando
-}
\ No newline at end of file
+pub mod dictionary;
+pub mod errors;
+pub mod locale;
+
+use crate::ast::{JsonValue, visitor::write_json_value};
+use crate::generator::GenerateOptions;
+use dictionary::Dictionaries;
+use errors::{TagArgumentError, UnknownLocaleError, UnknownTagError};
+use locale::LocaleData;
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagArg {
+    Value(JsonValue),
+    /// A `$variable` reference, bound to a value before the tag is evaluated.
+    Variable(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Tag {
+    pub name: String,
+    pub args: Vec<TagArg>,
+    /// Arguments given as `name=value`, e.g. `locale="de_DE"`.
+    pub named_args: Vec<(String, TagArg)>,
+}
+
+impl Tag {
+    pub fn named_arg(&self, name: &str) -> Option<&TagArg> {
+        self.named_args.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    /// Replaces every `$variable` argument with the value `lookup` gives for it.
+    pub fn bind<F>(&self, lookup: F) -> Result<Tag, Box<dyn Error>>
+        where F: Fn(&str) -> Result<JsonValue, Box<dyn Error>> {
+        let bind_arg = |arg: &TagArg| match arg {
+            TagArg::Variable(name) => lookup(name).map(TagArg::Value),
+            v => Ok(v.clone()),
+        };
+
+        Ok(Tag {
+            name: self.name.clone(),
+            args: self.args.iter().map(bind_arg).collect::<Result<_, _>>()?,
+            named_args: self.named_args.iter()
+                .map(|(n, arg)| bind_arg(arg).map(|v| (n.clone(), v)))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Formats the tag as written in a template, without the surrounding `{{ }}`.
+impl Display for Tag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let args: Vec<String> = self.args.iter().map(TagArg::to_string)
+            .chain(self.named_args.iter().map(|(name, arg)| format!("{}={}", name, arg)))
+            .collect();
+
+        write!(f, "{}({})", self.name, args.join(", "))
+    }
+}
+
+impl Display for TagArg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TagArg::Value(v) => {
+                let mut json = String::new();
+                write_json_value(v, &mut json);
+                f.write_str(&json)
+            },
+            TagArg::Variable(name) => write!(f, "${}", name),
+        }
+    }
+}
+
+/// A piece of a template string: literal text, a `{{tag()}}` or a `{{$variable}}` reference.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    Text(String),
+    Tag(Tag),
+    Variable(String),
+}
+
+/// State shared by every tag evaluated while generating a document.
+pub struct TagContext {
+    rng: StdRng,
+    dictionaries: Dictionaries,
+    locale: String,
+}
+
+impl TagContext {
+    pub fn new(options: &GenerateOptions) -> TagContext {
+        let rng = match options.seed {
+            Some(s) => StdRng::seed_from_u64(s),
+            None => StdRng::from_entropy(),
+        };
+
+        TagContext {
+            rng,
+            dictionaries: Dictionaries::new(options.dictionary_dir.clone()),
+            locale: options.locale.clone().unwrap_or_else(|| String::from(locale::DEFAULT_LOCALE)),
+        }
+    }
+
+    pub fn evaluate(&mut self, tag: &Tag) -> Result<JsonValue, Box<dyn Error>> {
+        match tag.name.as_str() {
+            "pick" => self.pick(tag),
+            "guid" => self.guid(tag),
+            "rand" => self.rand(tag),
+            "firstName" => self.choose_from_locale(tag, |l| l.first_names),
+            "lastName" => self.choose_from_locale(tag, |l| l.last_names),
+            "city" => self.choose_from_locale(tag, |l| l.cities),
+            "phone" => self.format_from_locale(tag, |l| l.phone_formats),
+            "postalCode" => self.format_from_locale(tag, |l| l.postal_code_formats),
+            name => Err(Box::new(UnknownTagError::new(name))),
+        }
+    }
+
+    fn pick(&mut self, tag: &Tag) -> Result<JsonValue, Box<dyn Error>> {
+        let name = match tag.args.as_slice() {
+            [TagArg::Value(JsonValue::String(name))] => name,
+            _ => return Err(Box::new(TagArgumentError::new(&tag.name, "Expected a dictionary name"))),
+        };
+
+        let dictionary = self.dictionaries.get(name)?;
+        match dictionary.choose(&mut self.rng) {
+            Some(word) => Ok(JsonValue::String(String::from(word))),
+            None => Err(Box::new(TagArgumentError::new(&tag.name, &format!("Dictionary {} is empty", name)))),
+        }
+    }
+
+    /// A random integer between `min` and `max` inclusive.
+    fn rand(&mut self, tag: &Tag) -> Result<JsonValue, Box<dyn Error>> {
+        match tag.args.as_slice() {
+            [TagArg::Value(JsonValue::Number(min)), TagArg::Value(JsonValue::Number(max))] if min <= max => {
+                let n = self.rng.gen_range(*min as i128, *max as i128 + 1);
+                Ok(JsonValue::Number(n as i64))
+            },
+            _ => Err(Box::new(TagArgumentError::new(&tag.name, "Expected min and max numbers with min <= max"))),
+        }
+    }
+
+    fn guid(&mut self, tag: &Tag) -> Result<JsonValue, Box<dyn Error>> {
+        if !tag.args.is_empty() || !tag.named_args.is_empty() {
+            return Err(Box::new(TagArgumentError::new(&tag.name, "Expected no arguments")));
+        }
+
+        // A version 4 UUID: random apart from the version and variant bits.
+        let mut bytes: [u8; 16] = self.rng.gen();
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(JsonValue::String(format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])))
+    }
+
+    /// The tag's `locale` argument, falling back to the locale given at generation time.
+    fn locale(&self, tag: &Tag) -> Result<&'static LocaleData, Box<dyn Error>> {
+        if !tag.args.is_empty() {
+            return Err(Box::new(TagArgumentError::new(&tag.name, "Expected no positional arguments")));
+        }
+
+        let name = match tag.named_arg("locale") {
+            Some(TagArg::Value(JsonValue::String(l))) => l.as_str(),
+            Some(_) => return Err(Box::new(TagArgumentError::new(&tag.name, "locale must be a string"))),
+            None => self.locale.as_str(),
+        };
+
+        locale::find(name).ok_or_else(|| Box::new(UnknownLocaleError::new(name)) as Box<dyn Error>)
+    }
+
+    fn choose_from_locale<F>(&mut self, tag: &Tag, data: F) -> Result<JsonValue, Box<dyn Error>>
+        where F: Fn(&LocaleData) -> &'static [&'static str] {
+        let values = data(self.locale(tag)?);
+        let value = values.choose(&mut self.rng).expect("locale data should not be empty");
+
+        Ok(JsonValue::String(String::from(*value)))
+    }
+
+    fn format_from_locale<F>(&mut self, tag: &Tag, formats: F) -> Result<JsonValue, Box<dyn Error>>
+        where F: Fn(&LocaleData) -> &'static [&'static str] {
+        let format = formats(self.locale(tag)?).choose(&mut self.rng).expect("locale data should not be empty");
+        let rng = &mut self.rng;
+        let value = format.chars()
+            .map(|c| if c == '#' { std::char::from_digit(rng.gen_range(0, 10), 10).unwrap() } else { c })
+            .collect();
+
+        Ok(JsonValue::String(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::node::Node;
+    use crate::parsing::{ParseOptions, errors::ParseError, parse_with_options};
+
+    fn tag(name: &str, args: Vec<JsonValue>) -> Segment {
+        Segment::Tag(Tag { name: String::from(name), args: args.into_iter().map(TagArg::Value).collect(), ..Tag::default() })
+    }
+
+    fn options(locale: Option<&str>) -> GenerateOptions {
+        GenerateOptions { seed: Some(1), locale: locale.map(String::from), ..GenerateOptions::default() }
+    }
+
+    /// Parses `s` as the contents of a template string.
+    fn parse_segments(s: &str) -> Result<Vec<Segment>, ParseError> {
+        crate::parsing::parse_segments(&format!("\"{}\"", s))
+    }
+
+    fn parse_tag(s: &str) -> Tag {
+        match parse_segments(s).unwrap().remove(0) {
+            Segment::Tag(t) => t,
+            s => panic!("expected a tag, got {:?}", s),
+        }
+    }
+
+    macro_rules! segments_theory {
+        ($name:ident, $input:literal, $($s:expr),*) => {
+            #[test]
+            fn $name() {
+                assert_eq!(parse_segments($input).unwrap(), vec![$($s),*]);
+            }
+        }
+    }
+
+    segments_theory!(plain_text_theory, "hello", Segment::Text(String::from("hello")));
+    segments_theory!(no_args_theory, "{{firstName()}}", tag("firstName", vec![]));
+    segments_theory!(no_parens_theory, "{{ firstName }}", tag("firstName", vec![]));
+    segments_theory!(pick_theory, "{{pick(\"colors\")}}", tag("pick", vec![JsonValue::String(String::from("colors"))]));
+    segments_theory!(mixed_args_theory, "{{foo(true, -123, \"hello\")}}",
+        tag("foo", vec![JsonValue::Boolean(true), JsonValue::Number(-123), JsonValue::String(String::from("hello"))]));
+    segments_theory!(fraction_args_theory, "{{pick(1.5, 2.50)}}", tag("pick", vec![
+        JsonValue::Float { value: 1.5, lexeme: String::from("1.5") },
+        JsonValue::Float { value: 2.5, lexeme: String::from("2.50") }]));
+    segments_theory!(exponent_args_theory, "{{f(-2e3, 1.5E-2, 0)}}", tag("f", vec![
+        JsonValue::Float { value: -2000.0, lexeme: String::from("-2e3") },
+        JsonValue::Float { value: 0.015, lexeme: String::from("1.5E-2") },
+        JsonValue::Number(0)]));
+    segments_theory!(escaped_exponent_args_theory, r"{{f(1\u0065\u002b2)}}", tag("f", vec![
+        JsonValue::Float { value: 100.0, lexeme: String::from("1e+2") }]));
+    segments_theory!(interpolated_theory, "a {{b()}} c",
+        Segment::Text(String::from("a ")),
+        tag("b", vec![]),
+        Segment::Text(String::from(" c")));
+
+    macro_rules! segments_err_theory {
+        ($name:ident, $input:literal) => {
+            #[test]
+            fn $name() {
+                assert!(parse_segments($input).is_err());
+            }
+        }
+    }
+
+    segments_err_theory!(unclosed_tag_theory, "{{pick(\"colors\")");
+    segments_err_theory!(missing_paren_theory, "{{pick(\"colors\"}}");
+    segments_err_theory!(empty_tag_theory, "{{}}");
+    segments_err_theory!(bad_arg_theory, "{{pick(colors)}}");
+    segments_err_theory!(positional_after_named_theory, "{{foo(a=1, 2)}}");
+    segments_err_theory!(variable_with_args_theory, "{{$tenant()}}");
+    segments_err_theory!(empty_variable_theory, "{{$}}");
+    segments_err_theory!(integer_arg_out_of_range_theory, "{{f(9223372036854775808)}}");
+    segments_err_theory!(float_arg_out_of_range_theory, "{{f(1e400)}}");
+    segments_err_theory!(trailing_dot_arg_theory, "{{f(1.)}}");
+    segments_err_theory!(leading_zero_arg_theory, "{{f(01)}}");
+
+    #[test]
+    fn args_follow_arbitrary_precision() {
+        let options = ParseOptions { arbitrary_precision: true, ..ParseOptions::default() };
+        let mut expr = parse_with_options("\"{{f(1e400, 12345678901234567890)}}\"", &options).unwrap();
+        let segments = match &mut expr {
+            Node::Template(t) => std::mem::take(&mut t.segments),
+            node => panic!("{:?}", node),
+        };
+
+        assert_eq!(segments, vec![tag("f", vec![
+            JsonValue::Decimal("1e400".parse().unwrap()),
+            JsonValue::Decimal("12345678901234567890".parse().unwrap())])]);
+    }
+
+    segments_theory!(variable_theory, "{{ $tenant }}", Segment::Variable(String::from("tenant")));
+    segments_theory!(interpolated_variable_theory, "id-{{$tenant}}",
+        Segment::Text(String::from("id-")),
+        Segment::Variable(String::from("tenant")));
+
+    #[test]
+    fn guid_is_version_4() {
+        let ctx = &mut TagContext::new(&options(None));
+
+        match ctx.evaluate(&parse_tag("{{guid()}}")).unwrap() {
+            JsonValue::String(g) => {
+                let groups: Vec<usize> = g.split('-').map(|p| p.len()).collect();
+                assert_eq!(groups, vec![8, 4, 4, 4, 12]);
+                assert_eq!(&g[14..15], "4");
+                assert!("89ab".contains(&g[19..20]));
+            },
+            v => panic!("expected a string, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn parse_named_args() {
+        let t = parse_tag("{{firstName(locale = \"de_DE\")}}");
+
+        assert!(t.args.is_empty());
+        assert_eq!(t.named_arg("locale"), Some(&TagArg::Value(JsonValue::String(String::from("de_DE")))));
+    }
+
+    #[test]
+    fn parse_variable_args() {
+        let t = parse_tag("{{rand($min, 5, max=$max)}}");
+
+        assert_eq!(t.args, vec![TagArg::Variable(String::from("min")), TagArg::Value(JsonValue::Number(5))]);
+        assert_eq!(t.named_arg("max"), Some(&TagArg::Variable(String::from("max"))));
+    }
+
+    #[test]
+    fn bind_replaces_variables() {
+        let t = parse_tag("{{rand($min, $max)}}").bind(|name| match name {
+            "min" => Ok(JsonValue::Number(1)),
+            _ => Ok(JsonValue::Number(2)),
+        }).unwrap();
+
+        assert_eq!(t.args, vec![TagArg::Value(JsonValue::Number(1)), TagArg::Value(JsonValue::Number(2))]);
+    }
+
+    #[test]
+    fn rand_is_within_bounds() {
+        let ctx = &mut TagContext::new(&options(None));
+
+        for _ in 0..20 {
+            match ctx.evaluate(&parse_tag("{{rand(-2, 2)}}")).unwrap() {
+                JsonValue::Number(n) => assert!((-2..=2).contains(&n)),
+                v => panic!("expected a number, got {:?}", v),
+            }
+        }
+
+        assert!(ctx.evaluate(&parse_tag("{{rand(2, 1)}}")).unwrap_err().is::<TagArgumentError>());
+    }
+
+    #[test]
+    fn locale_tag_uses_named_locale() {
+        let ctx = &mut TagContext::new(&options(None));
+        let german = locale::find("de_DE").unwrap();
+
+        for _ in 0..10 {
+            match ctx.evaluate(&parse_tag("{{city(locale=\"de_DE\")}}")).unwrap() {
+                JsonValue::String(c) => assert!(german.cities.contains(&c.as_str())),
+                v => panic!("expected a string, got {:?}", v),
+            }
+        }
+    }
+
+    #[test]
+    fn locale_tag_uses_generation_locale() {
+        let ctx = &mut TagContext::new(&options(Some("ja_JP")));
+        let japanese = locale::find("ja_JP").unwrap();
+
+        match ctx.evaluate(&parse_tag("{{lastName()}}")).unwrap() {
+            JsonValue::String(n) => assert!(japanese.last_names.contains(&n.as_str())),
+            v => panic!("expected a string, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn format_tag_replaces_digits() {
+        let ctx = &mut TagContext::new(&options(Some("ja_JP")));
+
+        match ctx.evaluate(&parse_tag("{{postalCode()}}")).unwrap() {
+            JsonValue::String(p) => {
+                assert_eq!(p.len(), 8);
+                assert!(p.chars().enumerate().all(|(i, c)| if i == 3 { c == '-' } else { c.is_ascii_digit() }));
+            },
+            v => panic!("expected a string, got {:?}", v),
+        }
+    }
+
+    #[test]
+    fn unknown_locale_is_err() {
+        let ctx = &mut TagContext::new(&options(Some("xx_XX")));
+
+        assert!(ctx.evaluate(&parse_tag("{{phone()}}")).unwrap_err().is::<UnknownLocaleError>());
+    }
+
+    #[test]
+    fn evaluate_unknown_tag_is_err() {
+        let ctx = &mut TagContext::new(&options(None));
+        let result = ctx.evaluate(&parse_tag("{{nope()}}"));
+
+        assert!(result.unwrap_err().is::<UnknownTagError>());
+    }
+
+    #[test]
+    fn pick_requires_dictionary_name() {
+        let ctx = &mut TagContext::new(&options(None));
+        let result = ctx.evaluate(&parse_tag("{{pick(1)}}"));
+
+        assert!(result.unwrap_err().is::<TagArgumentError>());
+    }
+}
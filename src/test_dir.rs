@@ -0,0 +1,62 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// A temporary directory of files for a test, deleted when dropped. Each has its own name, so
+/// tests running at the same time do not share one.
+pub struct TestDir {
+    path: PathBuf,
+}
+
+impl TestDir {
+    /// Creates the directory with `files`, given as paths relative to it and their contents.
+    pub fn new(name: &str, files: &[(&str, &str)]) -> TestDir {
+        let unique = format!("bjg_{}_{}_{}", name, std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed));
+        let dir = TestDir { path: std::env::temp_dir().join(unique) };
+
+        fs::create_dir_all(&dir.path).unwrap();
+        for (file, contents) in files {
+            let path = dir.path.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        dir
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn join(&self, path: &str) -> PathBuf {
+        self.path.join(path)
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dir_is_deleted_on_drop() {
+        let dir = TestDir::new("drop", &[("a/b.txt", "b")]);
+        let path = dir.path().to_path_buf();
+        assert_eq!(fs::read_to_string(dir.join("a/b.txt")).unwrap(), "b");
+
+        drop(dir);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_dirs_have_their_own_names() {
+        assert_ne!(TestDir::new("same", &[]).path(), TestDir::new("same", &[]).path());
+    }
+}
@@ -0,0 +1,925 @@
+use super::{Literal, Punct, Token, TokenKind};
+use crate::ast::span::{Position, Span};
+use crate::parsing::errors::ParseError;
+use std::borrow::Cow;
+use std::io::{BufRead, ErrorKind};
+
+const DIGIT: &[&str] = &["a digit"];
+const HEX_DIGIT: &[&str] = &["a hex digit"];
+const ESCAPE: &[&str] = &["'\"'", "'\\'", "'/'", "'b'", "'f'", "'n'", "'r'", "'t'", "'u'", "'{'"];
+const TAG_END: &[&str] = &["'}}'"];
+
+enum Input<'a> {
+    Str(&'a str),
+    Reader(Box<dyn BufRead + 'a>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mode {
+    Json,
+    /// Inside a `{{ }}` tag in a string closed by `quote`.
+    Tag { quote: char },
+}
+
+/// How a run of string text ended.
+enum StringEnd {
+    Quote,
+    Tag,
+}
+
+/// Splits UTF-8 input into tokens, ending with `TokenKind::Eof`. Malformed lexemes become
+/// `TokenKind::Error` tokens and lexing carries on after them, but a read error or invalid UTF-8
+/// ends the input.
+pub struct Lexer<'a> {
+    input: Input<'a>,
+    /// Lexes comments, single quoted strings, `\'` escapes and hex numbers.
+    lenient: bool,
+    /// Where the next character starts.
+    pos: Position,
+    /// The next character, when read ahead from a reader.
+    peeked: Option<char>,
+    /// The text of the current token, when it cannot be borrowed from the input.
+    buf: String,
+    /// The error that stopped the input being read.
+    failed: Option<ParseError>,
+    mode: Mode,
+    /// In tag mode, the next character with its escape decoded, and where it starts.
+    decoded: Option<(char, Position)>,
+    /// How many bytes a string, name or the text between tags may have once decoded.
+    max_string_length: Option<usize>,
+    done: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str, lenient: bool) -> Lexer<'a> {
+        Lexer::with_input(Input::Str(source), lenient)
+    }
+
+    /// Lexes `source` from `start`, which is on a character boundary and in between tokens. Spans
+    /// are positions in all of `source`.
+    pub fn starting_at(source: &'a str, lenient: bool, start: Position) -> Lexer<'a> {
+        Lexer { pos: start, ..Lexer::new(source, lenient) }
+    }
+
+    /// Reads from `reader` as tokens are needed. Token text is always owned.
+    pub fn from_reader(reader: impl BufRead + 'a, lenient: bool) -> Lexer<'a> {
+        Lexer::with_input(Input::Reader(Box::new(reader)), lenient)
+    }
+
+    fn with_input(input: Input<'a>, lenient: bool) -> Lexer<'a> {
+        Lexer { input, lenient, pos: Position::default(), peeked: None, buf: String::new(), failed: None, mode: Mode::Json, decoded: None, max_string_length: None, done: false }
+    }
+
+    /// Stops lexing, with a `ParseError::StringTooLong`, at the first string, name or text
+    /// between tags longer than `max` bytes, so that no more of it is read.
+    pub fn with_max_string_length(mut self, max: Option<usize>) -> Lexer<'a> {
+        self.max_string_length = max;
+        self
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        match &mut self.input {
+            Input::Str(s) => s[self.pos.offset..].chars().next(),
+            Input::Reader(_) if self.peeked.is_some() || self.failed.is_some() => self.peeked,
+            Input::Reader(_) => {
+                match self.read_char() {
+                    Ok(c) => self.peeked = c,
+                    Err(e) => self.failed = Some(e),
+                }
+                self.peeked
+            },
+        }
+    }
+
+    /// Consumes the character returned by `peek`.
+    fn bump(&mut self) -> char {
+        let c = self.peek().expect("there should be a character to consume");
+        if let Input::Reader(_) = self.input {
+            self.peeked = None;
+            self.buf.push(c);
+        }
+        self.pos = self.pos.after(c);
+        c
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_while(&mut self, f: impl Fn(char) -> bool) {
+        while self.peek().is_some_and(&f) {
+            self.bump();
+        }
+    }
+
+    /// The text from `start` to the current position.
+    fn text(&mut self, start: Position) -> Cow<'a, str> {
+        match self.input {
+            Input::Str(s) => Cow::Borrowed(&s[start.offset..self.pos.offset]),
+            Input::Reader(_) => Cow::Owned(std::mem::take(&mut self.buf)),
+        }
+    }
+
+    /// An error for the next character, which is not consumed.
+    fn unexpected(&mut self, expected: &[&'static str]) -> ParseError {
+        match self.peek() {
+            Some(c) => ParseError::UnexpectedToken {
+                found: c.escape_default().to_string(),
+                span: Span::new(self.pos, self.pos.offset + c.len_utf8()),
+                expected: expected.to_vec(),
+            },
+            None => ParseError::UnexpectedEndOfInput { span: Span::new(self.pos, self.pos.offset), expected: expected.to_vec() },
+        }
+    }
+
+    fn expect(&mut self, c: char, expected: &'static str) -> Result<(), ParseError> {
+        if self.eat(c) { Ok(()) } else { Err(self.unexpected(&[expected])) }
+    }
+
+    fn lex(&mut self, c: char) -> TokenKind<'a> {
+        let start = self.pos;
+
+        match c {
+            ' ' | '\t' | '\n' | '\r' => {
+                self.eat_while(|c| matches!(c, ' ' | '\t' | '\n' | '\r'));
+                TokenKind::Whitespace(self.text(start))
+            },
+            '"' | '\'' if c == '"' || self.lenient => {
+                self.bump();
+                self.string(c, true)
+            },
+            '-' | '0'..='9' => match number(&mut SourceChars(self)) {
+                Ok(()) => TokenKind::Number(self.text(start)),
+                Err(e) => TokenKind::Error(e),
+            },
+            '/' if self.lenient => match self.comment() {
+                Ok(()) => TokenKind::Comment(self.text(start)),
+                Err(e) => TokenKind::Error(e),
+            },
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let mut len = 0;
+                while let Some(c) = self.peek().filter(|&c| c.is_alphanumeric() || c == '_' || c == '$') {
+                    len += c.len_utf8();
+                    if let Err(e) = self.check_length(len, start) {
+                        return TokenKind::Error(e);
+                    }
+                    self.bump();
+                }
+                let word = self.text(start);
+                match Literal::from_word(&word) {
+                    Some(l) => TokenKind::Literal(l),
+                    None => TokenKind::Identifier(word),
+                }
+            },
+            c => {
+                self.bump();
+                Punct::from_char(c).map_or(TokenKind::Unknown(c), TokenKind::Punct)
+            },
+        }
+    }
+
+    /// Lexes string text up to the closing `quote` or the next tag, after the opening quote when
+    /// `head` is set and otherwise after a tag's `}}`. After an error the rest of the string is
+    /// skipped, so lexing resumes after it, unless the string is too long, which ends lexing.
+    fn string(&mut self, quote: char, head: bool) -> TokenKind<'a> {
+        match self.string_contents(quote) {
+            Ok((s, StringEnd::Quote)) => {
+                self.mode = Mode::Json;
+                if head { TokenKind::String(s) } else { TokenKind::TemplateTail(s) }
+            },
+            Ok((s, StringEnd::Tag)) => {
+                self.mode = Mode::Tag { quote };
+                if head { TokenKind::TemplateHead(s) } else { TokenKind::TemplateMiddle(s) }
+            },
+            Err(e) => {
+                self.mode = Mode::Json;
+                self.decoded = None;
+                if !e.is_fatal() {
+                    self.eat_while(|c| c != quote && c != '\n');
+                    self.eat(quote);
+                }
+                TokenKind::Error(e)
+            },
+        }
+    }
+
+    /// An error once a string or name starting at `start` reaches `len` bytes, when that is more
+    /// than `max_string_length`.
+    fn check_length(&self, len: usize, start: Position) -> Result<(), ParseError> {
+        match self.max_string_length {
+            Some(max) if len > max => Err(ParseError::StringTooLong { max, span: Span::new(start, self.pos.offset) }),
+            _ => Ok(()),
+        }
+    }
+
+    fn string_contents(&mut self, quote: char) -> Result<(Cow<'a, str>, StringEnd), ParseError> {
+        let start = self.pos;
+        // The decoded value once it differs from the source text, or always when reading.
+        let mut value = match self.input {
+            Input::Str(_) => None,
+            Input::Reader(_) => Some(String::new()),
+        };
+        // The decoded length so far.
+        let mut len = 0;
+
+        loop {
+            let end = self.pos.offset;
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.unexpected(&["the closing quote"])),
+            };
+
+            let ended = match c {
+                c if c == quote => Some(StringEnd::Quote),
+                // `\{` escapes a `{` that would otherwise start a tag.
+                '{' => {
+                    self.bump();
+                    if self.eat('{') {
+                        Some(StringEnd::Tag)
+                    } else {
+                        len += 1;
+                        self.check_length(len, start)?;
+                        None
+                    }
+                },
+                '\\' => {
+                    self.bump();
+                    let decoded = self.escape()?;
+                    len += decoded.len_utf8();
+                    self.check_length(len, start)?;
+                    value.get_or_insert_with(|| match self.input {
+                        Input::Str(s) => String::from(&s[start.offset..end]),
+                        Input::Reader(_) => String::new(),
+                    }).push(decoded);
+                    continue;
+                },
+                c if c < '\u{20}' => return Err(self.unexpected(&["a string character", "an escape"])),
+                _ => {
+                    len += c.len_utf8();
+                    self.check_length(len, start)?;
+                    self.bump();
+                    None
+                },
+            };
+
+            if let Some(ended) = ended {
+                if let StringEnd::Quote = ended {
+                    self.bump();
+                }
+                let s = match (value, &self.input) {
+                    (Some(v), _) => Cow::Owned(v),
+                    (None, Input::Str(s)) => Cow::Borrowed(&s[start.offset..end]),
+                    (None, Input::Reader(_)) => unreachable!("reader strings are always decoded"),
+                };
+                return Ok((s, ended));
+            }
+
+            if let Some(v) = value.as_mut() {
+                v.push(c);
+            }
+        }
+    }
+
+    /// Decodes an escape sequence after its `\`.
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let c = match self.peek() {
+            Some(c @ ('"' | '\\' | '/' | 'b' | 'f' | 'n' | 'r' | 't' | 'u' | '{')) => c,
+            Some('\'') if self.lenient => '\'',
+            _ => return Err(self.unexpected(ESCAPE)),
+        };
+        self.bump();
+
+        Ok(match c {
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'u' => {
+                let high = self.hex4()?;
+                let code = match high {
+                    0xD800..=0xDBFF => {
+                        // A high surrogate must be followed by an escaped low surrogate.
+                        self.expect('\\', "'\\'")?;
+                        self.expect('u', "'u'")?;
+                        let low_start = self.pos;
+
+                        match self.hex4()? {
+                            low @ 0xDC00..=0xDFFF => 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00),
+                            low => return Err(self.escape_error(low_start, low, "a low surrogate")),
+                        }
+                    },
+                    code => code,
+                };
+
+                // Lone low surrogates are not characters.
+                match std::char::from_u32(code) {
+                    Some(c) => c,
+                    None => {
+                        let start = Position { offset: self.pos.offset - 6, column: self.pos.column - 6, ..self.pos };
+                        return Err(self.escape_error(start, code, "a high surrogate"));
+                    },
+                }
+            },
+            c => c,
+        })
+    }
+
+    /// An error for the `\u` escape of `code` whose digits start at `start`.
+    fn escape_error(&self, start: Position, code: u32, expected: &'static str) -> ParseError {
+        ParseError::UnexpectedToken {
+            found: format!("\\u{:04x}", code),
+            span: Span::new(start, self.pos.offset),
+            expected: vec![expected],
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, ParseError> {
+        let mut code = 0;
+
+        for _ in 0..4 {
+            let digit = self.peek().and_then(|c| c.to_digit(16)).ok_or_else(|| self.unexpected(HEX_DIGIT))?;
+            self.bump();
+            code = (code << 4) | digit;
+        }
+
+        Ok(code)
+    }
+
+    /// Lexes a comment starting at its `/`.
+    fn comment(&mut self) -> Result<(), ParseError> {
+        self.bump();
+
+        if self.eat('/') {
+            self.eat_while(|c| c != '\n');
+            Ok(())
+        } else if self.eat('*') {
+            loop {
+                match self.peek() {
+                    None => return Err(self.unexpected(&["'*/'"])),
+                    Some('*') => {
+                        self.bump();
+                        if self.eat('/') {
+                            return Ok(());
+                        }
+                    },
+                    Some(_) => {
+                        self.bump();
+                    },
+                }
+            }
+        } else {
+            Err(self.unexpected(&["'/'", "'*'"]))
+        }
+    }
+
+    /// In tag mode, the next character of the string with its escape decoded, so that tags in
+    /// strings that are valid JSON may quote their arguments with `\"`.
+    fn tag_peek(&mut self) -> Result<Option<char>, ParseError> {
+        if let Some((c, _)) = self.decoded {
+            return Ok(Some(c));
+        }
+
+        let start = self.pos;
+        let c = match self.peek() {
+            Some('\\') => {
+                self.bump();
+                self.escape()?
+            },
+            Some(c) if c < '\u{20}' => return Err(self.unexpected(TAG_END)),
+            Some(c) => {
+                self.bump();
+                c
+            },
+            None => return Ok(None),
+        };
+
+        self.decoded = Some((c, start));
+        Ok(Some(c))
+    }
+
+    /// Consumes the character returned by `tag_peek`.
+    fn tag_bump(&mut self) -> char {
+        self.decoded.take().expect("there should be a character to consume").0
+    }
+
+    /// Consumes characters matching `f`, up to `max_string_length` bytes of them when `limited`.
+    fn tag_take_while(&mut self, f: impl Fn(char) -> bool, limited: bool) -> Result<String, ParseError> {
+        let start = self.next_pos();
+        let mut s = String::new();
+        while let Some(c) = self.tag_peek()?.filter(|&c| f(c)) {
+            if limited {
+                self.check_length(s.len() + c.len_utf8(), start)?;
+            }
+            s.push(self.tag_bump());
+        }
+        Ok(s)
+    }
+
+    /// Where the next character starts, which in tag mode may have been read already.
+    fn next_pos(&self) -> Position {
+        self.decoded.map_or(self.pos, |(_, p)| p)
+    }
+
+    /// An error for the next character in tag mode, which is not consumed.
+    fn tag_unexpected(&mut self, expected: &[&'static str]) -> ParseError {
+        match self.tag_peek() {
+            Ok(Some(c)) => ParseError::UnexpectedToken {
+                found: c.escape_default().to_string(),
+                span: Span::new(self.next_pos(), self.pos.offset),
+                expected: expected.to_vec(),
+            },
+            Ok(None) => ParseError::UnexpectedEndOfInput { span: Span::new(self.pos, self.pos.offset), expected: expected.to_vec() },
+            Err(e) => e,
+        }
+    }
+
+    /// Lexes the tag language: names, `$`, parentheses, commas, `=`, and quoted strings,
+    /// numbers and literals as arguments. `}}` switches back to lexing the string.
+    fn lex_tag(&mut self, quote: char) -> TokenKind<'a> {
+        match self.tag_token(quote) {
+            Ok(kind) => kind,
+            Err(e) => {
+                self.mode = Mode::Json;
+                self.decoded = None;
+                if !e.is_fatal() {
+                    self.eat_while(|c| c != quote && c != '\n');
+                    self.eat(quote);
+                }
+                TokenKind::Error(e)
+            },
+        }
+    }
+
+    fn tag_token(&mut self, quote: char) -> Result<TokenKind<'a>, ParseError> {
+        let c = match self.tag_peek()? {
+            Some(c) => c,
+            None => return Err(self.tag_unexpected(TAG_END)),
+        };
+
+        Ok(match c {
+            c if c.is_whitespace() => TokenKind::Whitespace(Cow::Owned(self.tag_take_while(char::is_whitespace, false)?)),
+            '}' => {
+                self.tag_bump();
+                if self.tag_peek()? != Some('}') {
+                    return Ok(TokenKind::Unknown('}'));
+                }
+                self.tag_bump();
+                self.string(quote, false)
+            },
+            '"' => {
+                let start = self.next_pos();
+                self.tag_bump();
+                let mut s = String::new();
+                loop {
+                    match self.tag_peek()? {
+                        Some('"') => break,
+                        // Escapes any character, such as a quote.
+                        Some('\\') => {
+                            self.tag_bump();
+                            if self.tag_peek()?.is_none() {
+                                return Err(self.tag_unexpected(&["'\"'"]));
+                            }
+                        },
+                        Some(_) => {},
+                        None => return Err(self.tag_unexpected(&["'\"'"])),
+                    }
+                    let c = self.tag_peek()?.expect("there should be a character");
+                    self.check_length(s.len() + c.len_utf8(), start)?;
+                    s.push(self.tag_bump());
+                }
+                self.tag_bump();
+                TokenKind::String(Cow::Owned(s))
+            },
+            '-' | '0'..='9' => {
+                let mut chars = TagChars { lexer: self, text: String::new() };
+                number(&mut chars)?;
+                TokenKind::Number(Cow::Owned(chars.text))
+            },
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let word = self.tag_take_while(|c| c.is_ascii_alphanumeric() || c == '_', true)?;
+                match Literal::from_word(&word) {
+                    Some(l) => TokenKind::Literal(l),
+                    None => TokenKind::Identifier(Cow::Owned(word)),
+                }
+            },
+            c => {
+                self.tag_bump();
+                Punct::from_tag_char(c).map_or(TokenKind::Unknown(c), TokenKind::Punct)
+            },
+        })
+    }
+
+    fn read_byte(&mut self) -> Result<Option<u8>, ParseError> {
+        let reader = match &mut self.input {
+            Input::Reader(r) => r,
+            Input::Str(_) => unreachable!("only readers are read from"),
+        };
+
+        loop {
+            match reader.fill_buf() {
+                Ok([]) => return Ok(None),
+                Ok(buf) => {
+                    let b = buf[0];
+                    reader.consume(1);
+                    return Ok(Some(b));
+                },
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(ParseError::Io { message: e.to_string(), span: Span::new(self.pos, self.pos.offset) }),
+            }
+        }
+    }
+
+    fn read_char(&mut self) -> Result<Option<char>, ParseError> {
+        let invalid = ParseError::InvalidUtf8 { span: Span::new(self.pos, self.pos.offset) };
+        let first = match self.read_byte()? {
+            Some(b) => b,
+            None => return Ok(None),
+        };
+
+        let len = match first {
+            0x00..=0x7F => 1,
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => return Err(invalid),
+        };
+
+        let mut bytes = [first, 0, 0, 0];
+        for b in bytes.iter_mut().take(len).skip(1) {
+            *b = self.read_byte()?.ok_or_else(|| invalid.clone())?;
+        }
+
+        // Also rejects overlong encodings and surrogates.
+        let c = std::str::from_utf8(&bytes[..len]).map_err(|_| invalid)?.chars().next().unwrap();
+
+        Ok(Some(c))
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        if self.done {
+            return None;
+        }
+
+        self.buf.clear();
+
+        let start = self.next_pos();
+        let kind = match (self.mode, self.peek()) {
+            (Mode::Tag { quote }, _) => self.lex_tag(quote),
+            (Mode::Json, Some(c)) => self.lex(c),
+            (Mode::Json, None) => {
+                self.done = true;
+                TokenKind::Eof
+            },
+        };
+
+        // A token cut short by a read error is incomplete, so the error replaces it.
+        let kind = match self.failed.take() {
+            Some(e) => {
+                self.done = true;
+                TokenKind::Error(e)
+            },
+            None => kind,
+        };
+        if let TokenKind::Error(e) = &kind {
+            self.done |= e.is_fatal();
+        }
+
+        Some(Token { kind, span: Span::new(start, self.next_pos().offset) })
+    }
+}
+
+/// Where `number` reads characters from: the source as it is, or a tag with its escapes decoded.
+trait Chars {
+    fn lenient(&self) -> bool;
+    fn peek(&mut self) -> Result<Option<char>, ParseError>;
+    /// Consumes the character returned by `peek`.
+    fn bump(&mut self);
+    /// An error for the next character, which is not consumed.
+    fn unexpected(&mut self, expected: &[&'static str]) -> ParseError;
+
+    fn eat(&mut self, c: char) -> Result<bool, ParseError> {
+        let found = self.peek()? == Some(c);
+        if found {
+            self.bump();
+        }
+        Ok(found)
+    }
+
+    fn eat_while(&mut self, f: impl Fn(char) -> bool) -> Result<(), ParseError> {
+        while self.peek()?.is_some_and(&f) {
+            self.bump();
+        }
+        Ok(())
+    }
+}
+
+struct SourceChars<'l, 'a>(&'l mut Lexer<'a>);
+
+impl Chars for SourceChars<'_, '_> {
+    fn lenient(&self) -> bool {
+        self.0.lenient
+    }
+
+    fn peek(&mut self) -> Result<Option<char>, ParseError> {
+        Ok(self.0.peek())
+    }
+
+    fn bump(&mut self) {
+        self.0.bump();
+    }
+
+    fn unexpected(&mut self, expected: &[&'static str]) -> ParseError {
+        self.0.unexpected(expected)
+    }
+}
+
+/// The characters of a tag, collecting the text consumed.
+struct TagChars<'l, 'a> {
+    lexer: &'l mut Lexer<'a>,
+    text: String,
+}
+
+impl Chars for TagChars<'_, '_> {
+    fn lenient(&self) -> bool {
+        self.lexer.lenient
+    }
+
+    fn peek(&mut self) -> Result<Option<char>, ParseError> {
+        self.lexer.tag_peek()
+    }
+
+    fn bump(&mut self) {
+        self.text.push(self.lexer.tag_bump());
+    }
+
+    fn unexpected(&mut self, expected: &[&'static str]) -> ParseError {
+        self.lexer.tag_unexpected(expected)
+    }
+}
+
+/// Lexes a number starting with a digit or `-`, checking it against the JSON grammar, both in
+/// values and in tags.
+fn number(chars: &mut impl Chars) -> Result<(), ParseError> {
+    chars.eat('-')?;
+
+    match chars.peek()? {
+        // No leading zeros.
+        Some('0') => {
+            chars.bump();
+            if chars.lenient() && matches!(chars.peek()?, Some('x') | Some('X')) {
+                chars.bump();
+                return digits(chars, |c| c.is_ascii_hexdigit(), HEX_DIGIT);
+            }
+            if chars.peek()?.is_some_and(|c| c.is_ascii_digit()) {
+                return Err(chars.unexpected(&["'.'", "'e'", "the end of the number"]));
+            }
+        },
+        Some('1'..='9') => chars.eat_while(|c| c.is_ascii_digit())?,
+        _ => return Err(chars.unexpected(DIGIT)),
+    }
+
+    if chars.eat('.')? {
+        digits(chars, |c| c.is_ascii_digit(), DIGIT)?;
+    }
+
+    if chars.eat('e')? || chars.eat('E')? {
+        if !chars.eat('+')? {
+            chars.eat('-')?;
+        }
+        digits(chars, |c| c.is_ascii_digit(), DIGIT)?;
+    }
+
+    Ok(())
+}
+
+/// Consumes one or more characters matching `f`.
+fn digits(chars: &mut impl Chars, f: impl Fn(char) -> bool, expected: &[&'static str]) -> Result<(), ParseError> {
+    if !chars.peek()?.is_some_and(&f) {
+        return Err(chars.unexpected(expected));
+    }
+
+    chars.eat_while(f)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, BufReader, Read};
+
+    fn kinds(lexer: Lexer) -> Vec<TokenKind> {
+        lexer.map(|t| t.kind).collect()
+    }
+
+    fn string(s: &str) -> TokenKind<'_> {
+        TokenKind::String(Cow::Borrowed(s))
+    }
+
+    fn number(s: &str) -> TokenKind<'_> {
+        TokenKind::Number(Cow::Borrowed(s))
+    }
+
+    fn ws(s: &str) -> TokenKind<'_> {
+        TokenKind::Whitespace(Cow::Borrowed(s))
+    }
+
+    macro_rules! lex_theory {
+        ($name:ident, $input:expr, $($t:expr),+) => {
+            #[test]
+            fn $name() {
+                assert_eq!(kinds(Lexer::new($input, false)), vec![$($t),+, TokenKind::Eof]);
+            }
+        }
+    }
+
+    lex_theory!(hello_world_theory, "{\"hello\": \"world\"}",
+        TokenKind::Punct(Punct::OpenCurlyBrace),
+        string("hello"),
+        TokenKind::Punct(Punct::Colon),
+        ws(" "),
+        string("world"),
+        TokenKind::Punct(Punct::CloseCurlyBrace));
+
+    fn ident(s: &str) -> TokenKind<'_> {
+        TokenKind::Identifier(Cow::Borrowed(s))
+    }
+
+    lex_theory!(complex_tag_theory, "{\"x\": \"{{foo(true, -123, \"hello\")}}\"}",
+        TokenKind::Punct(Punct::OpenCurlyBrace),
+        string("x"),
+        TokenKind::Punct(Punct::Colon),
+        ws(" "),
+        TokenKind::TemplateHead(Cow::Borrowed("")),
+        ident("foo"),
+        TokenKind::Punct(Punct::OpenParenthesis),
+        TokenKind::Literal(Literal::True),
+        TokenKind::Punct(Punct::Comma),
+        ws(" "),
+        number("-123"),
+        TokenKind::Punct(Punct::Comma),
+        ws(" "),
+        string("hello"),
+        TokenKind::Punct(Punct::CloseParenthesis),
+        TokenKind::TemplateTail(Cow::Borrowed("")),
+        TokenKind::Punct(Punct::CloseCurlyBrace));
+
+    lex_theory!(tag_numbers_theory, "\"{{f(1.5, -2.5e+3, 0, 1E400)}}\"",
+        TokenKind::TemplateHead(Cow::Borrowed("")),
+        ident("f"),
+        TokenKind::Punct(Punct::OpenParenthesis),
+        number("1.5"),
+        TokenKind::Punct(Punct::Comma),
+        ws(" "),
+        number("-2.5e+3"),
+        TokenKind::Punct(Punct::Comma),
+        ws(" "),
+        number("0"),
+        TokenKind::Punct(Punct::Comma),
+        ws(" "),
+        number("1E400"),
+        TokenKind::Punct(Punct::CloseParenthesis),
+        TokenKind::TemplateTail(Cow::Borrowed("")));
+
+    lex_theory!(interpolated_tags_theory, "\"a {{$x}} b {{f(n=\\\"q\\\")}} c\"",
+        TokenKind::TemplateHead(Cow::Borrowed("a ")),
+        TokenKind::Punct(Punct::Dollar),
+        ident("x"),
+        TokenKind::TemplateMiddle(Cow::Borrowed(" b ")),
+        ident("f"),
+        TokenKind::Punct(Punct::OpenParenthesis),
+        ident("n"),
+        TokenKind::Punct(Punct::Equals),
+        string("q"),
+        TokenKind::Punct(Punct::CloseParenthesis),
+        TokenKind::TemplateTail(Cow::Borrowed(" c")));
+
+    lex_theory!(escaped_braces_theory, "\"\\{{x}} {a}\"",
+        TokenKind::String(Cow::Owned(String::from("{{x}} {a}"))));
+
+    lex_theory!(values_theory, "[-1.5e+3, 0,true false\r\n\tnull]",
+        TokenKind::Punct(Punct::OpenSquareBrace),
+        number("-1.5e+3"),
+        TokenKind::Punct(Punct::Comma),
+        ws(" "),
+        number("0"),
+        TokenKind::Punct(Punct::Comma),
+        TokenKind::Literal(Literal::True),
+        ws(" "),
+        TokenKind::Literal(Literal::False),
+        ws("\r\n\t"),
+        TokenKind::Literal(Literal::Null),
+        TokenKind::Punct(Punct::CloseSquareBrace));
+
+    lex_theory!(words_and_unknown_theory, "nul truex (é",
+        TokenKind::Identifier(Cow::Borrowed("nul")),
+        ws(" "),
+        TokenKind::Identifier(Cow::Borrowed("truex")),
+        ws(" "),
+        TokenKind::Unknown('('),
+        TokenKind::Identifier(Cow::Borrowed("é")));
+
+    #[test]
+    fn strings_without_escapes_are_borrowed() {
+        let tokens: Vec<_> = Lexer::new("\"plain\" \"a\\nb\"", false).collect();
+
+        assert!(matches!(&tokens[0].kind, TokenKind::String(Cow::Borrowed("plain"))));
+        assert!(matches!(&tokens[2].kind, TokenKind::String(Cow::Owned(s)) if s == "a\nb"));
+    }
+
+    #[test]
+    fn tokens_have_spans() {
+        let spans: Vec<Span> = Lexer::new("{\"é\":\n 12}", false).map(|t| t.span).collect();
+        let span = |start, end, line, column| Span { start, end, line, column };
+
+        assert_eq!(spans, vec![
+            span(0, 1, 1, 1),
+            span(1, 5, 1, 2),
+            span(5, 6, 1, 5),
+            span(6, 8, 1, 6),
+            span(8, 10, 2, 2),
+            span(10, 11, 2, 4),
+            span(11, 11, 2, 5),
+        ]);
+    }
+
+    #[test]
+    fn tag_tokens_have_spans() {
+        let spans: Vec<(usize, usize)> = Lexer::new("\"é{{ f }}\"", false).map(|t| (t.span.start, t.span.end)).collect();
+
+        assert_eq!(spans, vec![(0, 5), (5, 6), (6, 7), (7, 8), (8, 11), (11, 11)]);
+    }
+
+    #[test]
+    fn unclosed_tags_are_errors_and_lexing_resumes() {
+        let tokens = kinds(Lexer::new("\"{{f(\n1", false));
+
+        assert!(matches!(&tokens[3], TokenKind::Error(ParseError::UnexpectedToken { found, .. }) if found == "\\n"));
+        assert_eq!(tokens[4..], [ws("\n"), number("1"), TokenKind::Eof]);
+    }
+
+    #[test]
+    fn bad_strings_are_errors_and_lexing_resumes() {
+        let tokens = kinds(Lexer::new("\"a\\x\" 1", false));
+
+        assert!(matches!(&tokens[0], TokenKind::Error(ParseError::UnexpectedToken { found, .. }) if found == "x"));
+        assert_eq!(tokens[1..], [ws(" "), number("1"), TokenKind::Eof]);
+    }
+
+    #[test]
+    fn lenient_lexes_comments_single_quotes_and_hex() {
+        let tokens = kinds(Lexer::new("// a\n'b\\'' 0x1F/* c */", true));
+
+        assert_eq!(tokens, vec![
+            TokenKind::Comment(Cow::Borrowed("// a")),
+            ws("\n"),
+            TokenKind::String(Cow::Owned(String::from("b'"))),
+            ws(" "),
+            number("0x1F"),
+            TokenKind::Comment(Cow::Borrowed("/* c */")),
+            TokenKind::Eof,
+        ]);
+    }
+
+    #[test]
+    fn reader_matches_str() {
+        let s = "{\"straße\": [1, \"東京\", \"😀\\n\", \"a{{f(\\\"é\\\", 2)}}b\"]}\n";
+        let from_reader: Vec<Token> = Lexer::from_reader(s.as_bytes(), false).collect();
+
+        assert_eq!(from_reader, Lexer::new(s, false).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn reader_reports_invalid_utf8_offset() {
+        for bytes in [&b"ab\xFFc"[..], &b"ab\xC3"[..], &b"ab\xC0\xAF"[..], &b"ab\xED\xA0\x80"[..]].iter() {
+            let tokens: Vec<Token> = Lexer::from_reader(*bytes, false).collect();
+
+            assert_eq!(tokens.len(), 1);
+            assert!(matches!(&tokens[0].kind, TokenKind::Error(ParseError::InvalidUtf8 { span }) if span.start == 2));
+        }
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    #[test]
+    fn reader_reports_io_errors_once() {
+        let tokens = kinds(Lexer::from_reader(BufReader::new(FailingReader), false));
+
+        assert_eq!(tokens.len(), 1);
+        assert!(matches!(&tokens[0], TokenKind::Error(ParseError::Io { message, .. }) if message == "disk on fire"));
+    }
+}